@@ -0,0 +1,325 @@
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    core::usb::{
+        env::EnvFormat,
+        models::{AnchorRoot, BindStrategy},
+    },
+    infra::settings::SettingsOverrides,
+};
+
+// DoraTool 命令行入口
+// 不带子命令时等价于 `doratool serve`，保持以前"直接启动服务"的行为
+#[derive(Debug, Parser)]
+#[command(
+    name = "doratool",
+    version,
+    about = "USB role resolver for dora-rs robots"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Commands {
+    /// Start the web server and the background USB monitor
+    // 启动 Web 服务和后台 USB 监听
+    Serve(ServeArgs),
+
+    /// Scan once and list all USB devices with their bound roles
+    // 扫描一次并列出所有 USB 设备及其绑定的角色
+    List {
+        /// Print as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Explain why a device is (or is not) bound: every rule, criterion by criterion
+    // 解释设备的匹配过程：逐条规则、逐项条件列出比对结果
+    Explain {
+        /// Device id as shown by `doratool list`
+        device_id: String,
+        /// Print as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print the system path of the device currently bound to a role
+    // 输出某个角色当前绑定设备的系统路径
+    Resolve {
+        /// Role name, e.g. `top_camera`
+        role: String,
+    },
+
+    /// Check that required roles are online; exits non-zero when any is missing
+    // 检查必需的角色是否在线，缺失时返回非零退出码 (用于机器人启动脚本)
+    Check {
+        /// Roles to require (default: the rules marked as required)
+        roles: Vec<String>,
+        /// Print as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print `DORA_ROLE_<ROLE>=<path>` assignments for every bound role
+    // 输出所有已绑定角色的环境变量 (DORA_ROLE_<ROLE>=<路径>)
+    Env {
+        /// Output format: shell, dotenv, systemd or json
+        #[arg(long, default_value_t = EnvFormat::Shell)]
+        format: EnvFormat,
+    },
+
+    /// Block until every listed role is bound (e.g. in launch scripts after boot)
+    // 阻塞等待所有角色上线 (例如开机后的启动脚本)
+    WaitFor {
+        /// Role names
+        #[arg(required = true)]
+        roles: Vec<String>,
+        /// How long to wait, e.g. `30s` or `2m`
+        #[arg(long, default_value = "30s", value_parser = humantime::parse_duration)]
+        timeout: Duration,
+    },
+
+    /// Run a command with role paths substituted into its arguments and environment
+    // 启动子进程：把角色路径替换进参数 (`{alias}`)，并通过环境变量传入
+    Exec {
+        /// Role alias, e.g. `cam=top_camera`; `{cam}` in the command becomes its path
+        #[arg(long = "role", value_name = "ALIAS=ROLE", value_parser = parse_key_value)]
+        roles: Vec<(String, String)>,
+        /// Extra environment variable for the command; `{alias}` placeholders are substituted
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        envs: Vec<(String, String)>,
+        /// How long to wait for the roles before starting, e.g. `30s`
+        #[arg(long, default_value = "0s", value_parser = humantime::parse_duration)]
+        timeout: Duration,
+        /// Restart the command when a role's device is unplugged and plugged back in
+        #[arg(long)]
+        restart: bool,
+        /// Command to run
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+
+    /// Wait for the next plugged-in device and bind it to a role
+    // 学习模式：等待下一个插入的设备并绑定到角色
+    Learn {
+        /// Role name
+        role: String,
+        /// Binding strategy: port, serial, port_serial, vid_pid or topology (default: port if known)
+        #[arg(long)]
+        strategy: Option<BindStrategy>,
+        /// Seconds to wait for a device
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+
+    /// Manage the persistent rules in usb_rules.json
+    // 管理 usb_rules.json 中的持久化规则
+    Rules {
+        #[command(subcommand)]
+        action: RulesCommand,
+    },
+
+    /// Render or validate `${role:NAME}` placeholders in a dora dataflow
+    // 处理 dora 数据流 (dataflow.yml) 中的 `${role:NAME}` 占位符
+    Dataflow {
+        #[command(subcommand)]
+        action: DataflowCommand,
+    },
+
+    /// Run the server as a background daemon
+    // 以守护进程方式运行服务
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RulesCommand {
+    /// Add a new rule (the role must be unique)
+    // 添加一条新规则 (角色必须唯一)
+    Add(RuleArgs),
+
+    /// Bind a connected device to a role (the rule is generated from the device)
+    // 把在线设备绑定到角色 (根据设备信息生成规则)
+    Bind {
+        /// Device id as shown by `doratool list`
+        device_id: String,
+        /// Role name
+        role: String,
+        /// Binding strategy: port, serial, port_serial, vid_pid or topology (default: port if known)
+        #[arg(long)]
+        strategy: Option<BindStrategy>,
+    },
+
+    /// Remove the rule of a role
+    // 删除某个角色的规则
+    Remove {
+        /// Role name
+        role: String,
+    },
+
+    /// Show all saved rules
+    // 显示所有已保存的规则
+    Show {
+        /// Print as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DataflowCommand {
+    /// Replace placeholders with the currently resolved device paths
+    // 把占位符替换为当前解析出的设备路径
+    Render {
+        /// Dataflow file, e.g. `dataflow.yml`
+        input: PathBuf,
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// List the roles referenced by the dataflow and report unresolved ones
+    // 列出数据流引用的角色，有未解析的角色时报错
+    Validate {
+        /// Dataflow file, e.g. `dataflow.yml`
+        input: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DaemonCommand {
+    /// Fork into the background and start serving
+    // 转入后台并启动服务
+    Start(ServeArgs),
+
+    /// Stop the running daemon (SIGTERM, then SIGKILL after the timeout)
+    // 停止守护进程 (先 SIGTERM，超时后 SIGKILL)
+    Stop {
+        /// Seconds to wait for a graceful exit before sending SIGKILL
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+    },
+
+    /// Show whether the daemon is running
+    // 查看守护进程是否在运行
+    Status,
+
+    /// Stop the running daemon (if any) and start a new one
+    // 重启守护进程
+    Restart {
+        /// Seconds to wait for a graceful exit before sending SIGKILL
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+
+        #[command(flatten)]
+        serve: ServeArgs,
+    },
+}
+
+/// Command line overrides for settings.json (see `infra::settings`)
+// 覆盖 settings.json 的命令行参数
+#[derive(Debug, Clone, Default, Args)]
+pub struct ServeArgs {
+    /// Listen address, e.g. 127.0.0.1
+    #[arg(long)]
+    pub bind: Option<String>,
+
+    /// Listen port
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Sleep between two USB scans, in milliseconds (at least 50)
+    #[arg(long)]
+    pub scan_interval_ms: Option<u64>,
+
+    /// Scans slower than this are reported as I/O blocking, in milliseconds (at least 100)
+    #[arg(long)]
+    pub slow_scan_threshold_ms: Option<u64>,
+
+    /// Directory for the rolling log files
+    #[arg(long)]
+    pub log_dir: Option<PathBuf>,
+
+    /// Log filter, e.g. `info` or `doratool=debug`
+    #[arg(long)]
+    pub log_level: Option<String>,
+}
+
+impl From<ServeArgs> for SettingsOverrides {
+    fn from(args: ServeArgs) -> Self {
+        Self {
+            bind: args.bind,
+            port: args.port,
+            scan_interval_ms: args.scan_interval_ms,
+            slow_scan_threshold_ms: args.slow_scan_threshold_ms,
+            log_dir: args.log_dir,
+            log_level: args.log_level,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct RuleArgs {
+    /// Role name (unique)
+    pub role: String,
+
+    /// Vendor id, hex (`0x2e8a` or `2e8a`)
+    #[arg(long, value_parser = parse_hex_u16)]
+    pub vid: u16,
+
+    /// Product id, hex (`0x000a` or `000a`)
+    #[arg(long, value_parser = parse_hex_u16)]
+    pub pid: u16,
+
+    /// Serial number
+    #[arg(long)]
+    pub serial: Option<String>,
+
+    /// Physical port path, as shown by `doratool list` (udev `ID_PATH` on Linux)
+    /// (glob patterns allowed, e.g. `pci-0000:00:14.0-usb-0:2.*` or `*-usb-0:1.4:1.0`)
+    #[arg(long)]
+    pub port_path: Option<String>,
+
+    /// Topology anchor: `controller=<path>[#<root_hub>]` or `hub=<vid>:<pid>[:<serial>]`
+    #[arg(long, requires = "chain")]
+    pub anchor: Option<AnchorRoot>,
+
+    /// Port chain relative to the anchor, e.g. `2.1` (glob patterns allowed)
+    #[arg(long, requires = "anchor")]
+    pub chain: Option<String>,
+
+    /// Binding strategy: port, serial, port_serial, vid_pid or topology
+    /// (default: inferred from the given --serial / --port-path / --anchor)
+    #[arg(long)]
+    pub strategy: Option<BindStrategy>,
+
+    /// Priority when several rules match the same device (higher wins)
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    pub priority: i32,
+
+    /// Mark the role as required hardware (see `doratool check`)
+    #[arg(long)]
+    pub required: bool,
+}
+
+// 解析十六进制的 VID/PID，兼容 `0x` 前缀 (与前端显示的 "0x3290" 保持一致)
+fn parse_hex_u16(s: &str) -> Result<u16, String> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u16::from_str_radix(digits, 16).map_err(|e| format!("invalid hex id '{}': {}", s, e))
+}
+
+// 解析 `KEY=VALUE` 形式的参数 (例如 `--role cam=top_camera`)
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", s)),
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+
+use crate::{
+    cli::commands::{DaemonCommand, ServeArgs},
+    infra::{
+        config::AppPaths,
+        daemon::{self, DaemonStatus},
+        settings::Settings,
+    },
+};
+
+pub fn execute(action: DaemonCommand) -> Result<()> {
+    let paths = AppPaths::new()?;
+
+    match action {
+        DaemonCommand::Start(args) => start(paths, args),
+        DaemonCommand::Stop { timeout } => {
+            daemon::stop(&paths.pid_file, Duration::from_secs(timeout))
+        }
+        DaemonCommand::Status => status(&paths),
+        DaemonCommand::Restart { timeout, serve } => {
+            if let DaemonStatus::Running(_) = daemon::status(&paths.pid_file)? {
+                daemon::stop(&paths.pid_file, Duration::from_secs(timeout))?;
+            }
+            start(paths, serve)
+        }
+    }
+}
+
+/// `doratool daemon start`
+/// 配置在 fork 之前加载 (错误可以直接显示在终端上)，fork 之后才创建 tokio runtime
+/// 前台进程等到守护进程监听成功 (或启动失败) 后才返回
+fn start(paths: AppPaths, args: ServeArgs) -> Result<()> {
+    let settings = Settings::load(&paths.settings_file, args.into())?;
+
+    let ready = daemon::daemonize(&paths, &settings.log_dir(&paths))?;
+
+    super::serve(paths, settings, || ready.notify())
+}
+
+/// `doratool daemon status`
+/// 未运行时返回错误 (退出码非 0)，方便脚本判断
+fn status(paths: &AppPaths) -> Result<()> {
+    match daemon::status(&paths.pid_file)? {
+        DaemonStatus::Running(pid) => {
+            println!("dora-tool is running (pid {})", pid);
+            Ok(())
+        }
+        DaemonStatus::Stopped => bail!("dora-tool is not running"),
+    }
+}
@@ -0,0 +1,142 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    cli::commands::DataflowCommand,
+    core::usb::{
+        dataflow::{self, RoleReference},
+        manager,
+        models::ResolvedRole,
+        service,
+    },
+    infra::config::{self, AppPaths},
+};
+
+pub fn execute(action: DataflowCommand) -> Result<()> {
+    match action {
+        DataflowCommand::Render { input, output } => render(&input, output.as_deref()),
+        DataflowCommand::Validate { input } => validate(&input),
+    }
+}
+
+/// `doratool dataflow render in.yml -o out.yml`
+/// 有任何角色无法解析时报错，不会输出一个部分替换的数据流
+fn render(input: &Path, output: Option<&Path>) -> Result<()> {
+    let (text, statuses) = load(input)?;
+
+    let unresolved = unresolved_roles(&statuses);
+    if !unresolved.is_empty() {
+        bail!(
+            "unresolved roles: {} (see `doratool dataflow validate {}`)",
+            unresolved.join(", "),
+            input.display()
+        );
+    }
+
+    let resolved: Vec<ResolvedRole> = statuses.into_iter().filter_map(|s| s.resolved).collect();
+    let rendered = dataflow::render(&text, &resolved)?;
+    match output {
+        // 写临时文件再 rename，失败时不会留下写了一半的数据流
+        Some(path) => config::write_atomic(path, rendered.as_bytes())
+            .with_context(|| format!("failed to write {}", path.display()))?,
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
+
+/// `doratool dataflow validate in.yml`
+fn validate(input: &Path) -> Result<()> {
+    let (_, statuses) = load(input)?;
+
+    if statuses.is_empty() {
+        println!("no role placeholders in {}", input.display());
+        return Ok(());
+    }
+
+    println!("{:<20} {:<16} {:<12} PATH", "ROLE", "STATUS", "LINES");
+    for status in &statuses {
+        let lines: Vec<String> = status
+            .reference
+            .lines
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        println!(
+            "{:<20} {:<16} {:<12} {}",
+            status.reference.role,
+            status.problem.unwrap_or("ok"),
+            lines.join(","),
+            status.resolved.as_ref().map_or("-", |r| r.path.as_str())
+        );
+        if let Some(conflict) = &status.conflict {
+            eprintln!("warning: {}", conflict);
+        }
+    }
+
+    let unresolved = unresolved_roles(&statuses);
+    if !unresolved.is_empty() {
+        bail!("unresolved roles: {}", unresolved.join(", "));
+    }
+
+    Ok(())
+}
+
+// 数据流引用的一个角色的解析结果
+struct RoleCheck {
+    reference: RoleReference,
+    resolved: Option<ResolvedRole>,
+    // 未解析的原因：not configured / ambiguous / offline
+    problem: Option<&'static str>,
+    conflict: Option<String>,
+}
+
+// 读取数据流，本地扫描一次并解析其中引用的每个角色
+fn load(input: &Path) -> Result<(String, Vec<RoleCheck>)> {
+    let text =
+        fs::read_to_string(input).with_context(|| format!("failed to read {}", input.display()))?;
+    let references = dataflow::referenced_roles(&text)
+        .with_context(|| format!("invalid dataflow {}", input.display()))?;
+
+    let paths = AppPaths::new()?;
+    let rules = config::load_rules(&paths.config_file)?;
+    let raw_devices = manager::scan_once()?;
+    let conflicts = service::detect_conflicts(&raw_devices, &rules);
+
+    let statuses = references
+        .into_iter()
+        .map(|reference| {
+            let resolved = service::resolve_role(&raw_devices, &rules, &reference.role);
+            let conflict = conflicts
+                .iter()
+                .find(|c| c.involves_role(&reference.role))
+                .map(|c| c.to_string());
+            let problem = if resolved.is_some() {
+                None
+            } else if !rules.iter().any(|r| r.role == reference.role) {
+                Some("not configured")
+            } else if conflict.is_some() {
+                Some("ambiguous")
+            } else {
+                Some("offline")
+            };
+            RoleCheck {
+                reference,
+                resolved,
+                problem,
+                conflict,
+            }
+        })
+        .collect();
+
+    Ok((text, statuses))
+}
+
+fn unresolved_roles(statuses: &[RoleCheck]) -> Vec<&str> {
+    statuses
+        .iter()
+        .filter(|s| s.resolved.is_none())
+        .map(|s| s.reference.role.as_str())
+        .collect()
+}
@@ -0,0 +1,286 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+use crossbeam_channel::{Receiver, unbounded};
+use usb_resolver::{DeviceEvent, get_monitor};
+
+use crate::{
+    core::usb::{
+        env::{self, EnvFormat},
+        events::now_ms,
+        manager,
+        models::{DeviceConfig, DeviceView, ResolvedRole},
+        service,
+    },
+    infra::config::{self, AppPaths},
+};
+
+/// 本地扫描一次，并与已保存的规则进行匹配
+/// 不依赖正在运行的 Web 服务
+fn scan_views() -> Result<Vec<DeviceView>> {
+    let paths = AppPaths::new()?;
+    let rules = config::load_rules(&paths.config_file)?;
+    let raw_devices = manager::scan_once()?;
+
+    Ok(service::match_raw_to_views(&raw_devices, &rules))
+}
+
+/// `doratool list`
+pub fn list(json: bool) -> Result<()> {
+    let views = scan_views()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&views)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<50} {:<20} {:<8} {:<8} {:<20} {:<40} SYSTEM_PATH",
+        "ID", "ROLE", "VID", "PID", "SERIAL", "PORT_PATH"
+    );
+    for view in &views {
+        println!(
+            "{:<50} {:<20} {:<8} {:<8} {:<20} {:<40} {}",
+            view.id,
+            view.role.as_deref().unwrap_or("-"),
+            view.vid,
+            view.pid,
+            view.serial.as_deref().unwrap_or("-"),
+            view.port_path,
+            view.system_path
+        );
+    }
+
+    // 冲突的设备不会绑定角色，在表格下方说明原因
+    let mut conflicts = Vec::new();
+    for conflict in views.iter().flat_map(|v| &v.conflicts) {
+        if !conflicts.contains(&conflict) {
+            conflicts.push(conflict);
+        }
+    }
+    for conflict in conflicts {
+        eprintln!("warning: {}", conflict);
+    }
+
+    Ok(())
+}
+
+/// `doratool explain <device_id>`
+pub fn explain(device_id: &str, json: bool) -> Result<()> {
+    let paths = AppPaths::new()?;
+    let rules = config::load_rules(&paths.config_file)?;
+    let raw_devices = manager::scan_once()?;
+
+    let Some(explanation) = service::explain_device(&raw_devices, &rules, device_id) else {
+        bail!("device '{}' not found", device_id);
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&explanation)?);
+        return Ok(());
+    }
+
+    let device = &explanation.device;
+    println!("device: {}", device.id);
+    println!("role:   {}", device.role.as_deref().unwrap_or("-"));
+    for conflict in &device.conflicts {
+        println!("conflict: {}", conflict);
+    }
+    if explanation.rules.is_empty() {
+        println!("no rules configured");
+    }
+
+    for rule in &explanation.rules {
+        let status = if rule.selected {
+            "selected"
+        } else if rule.matched {
+            "matched (not selected)"
+        } else {
+            "no match"
+        };
+        println!();
+        println!(
+            "rule '{}' ({}, priority {}): {}",
+            rule.role, rule.strategy, rule.priority, status
+        );
+        for check in &rule.checks {
+            println!(
+                "  {} {:<10} expected {:<24} actual {}",
+                if check.passed { "✓" } else { "✗" },
+                check.criterion.to_string(),
+                check.expected.as_deref().unwrap_or("-"),
+                check.actual.as_deref().unwrap_or("-")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// `doratool check [roles...]`
+/// 本地扫描一次，列出角色状态；必需的角色 (参数指定，或规则里标记 required) 缺失时报错退出
+pub fn check(roles: &[String], json: bool) -> Result<()> {
+    let paths = AppPaths::new()?;
+    let rules = config::load_rules(&paths.config_file)?;
+    let raw_devices = manager::scan_once()?;
+
+    for role in roles {
+        if !rules.iter().any(|r| &r.role == role) {
+            bail!("role '{}' is not configured", role);
+        }
+    }
+
+    let mut statuses = service::role_statuses(&raw_devices, &rules, &HashMap::new(), now_ms());
+    if !roles.is_empty() {
+        statuses.retain(|s| roles.contains(&s.role));
+        for status in &mut statuses {
+            status.required = true;
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&statuses)?);
+    } else {
+        println!(
+            "{:<20} {:<8} {:<8} SYSTEM_PATH",
+            "ROLE", "REQUIRED", "STATUS"
+        );
+        for status in &statuses {
+            println!(
+                "{:<20} {:<8} {:<8} {}",
+                status.role,
+                if status.required { "yes" } else { "no" },
+                if status.online { "online" } else { "offline" },
+                status.system_path.as_deref().unwrap_or("-")
+            );
+            for conflict in &status.conflicts {
+                eprintln!("warning: {}", conflict);
+            }
+        }
+    }
+
+    let missing = service::missing_required(&statuses);
+    if !missing.is_empty() {
+        bail!("missing required roles: {}", missing.join(", "));
+    }
+
+    Ok(())
+}
+
+/// `doratool env [--format shell|dotenv|systemd|json]`
+/// 例如 `eval "$(doratool env)"` 或 `doratool env --format dotenv > .env`
+pub fn env(format: EnvFormat) -> Result<()> {
+    let paths = AppPaths::new()?;
+    let rules = config::load_rules(&paths.config_file)?;
+    let raw_devices = manager::scan_once()?;
+
+    let resolved = service::resolve_roles(&raw_devices, &rules);
+    let vars = env::env_vars(&resolved)?;
+    print!("{}", env::render(&vars, format));
+
+    Ok(())
+}
+
+/// `doratool wait-for <roles...> --timeout 30s`
+/// 监听内核的设备事件 (不依赖正在运行的 Web 服务)，每次有变化就重新扫描检查，
+/// 所有角色都绑定后输出路径；超时报错并列出仍然缺失的角色
+pub fn wait_for(roles: &[String], timeout: Duration) -> Result<()> {
+    let paths = AppPaths::new()?;
+    let rules = config::load_rules(&paths.config_file)?;
+
+    for role in roles {
+        if !rules.iter().any(|r| &r.role == role) {
+            bail!("role '{}' is not configured", role);
+        }
+    }
+
+    let (tx, rx) = unbounded();
+    // 先开始监听再扫描，避免扫描之后插入的设备被漏掉
+    get_monitor().start(tx)?;
+
+    let resolved = wait_resolved(&rules, roles, &rx, Some(timeout))?;
+    if let [single] = resolved.as_slice() {
+        println!("{}", single.path);
+    } else {
+        for r in &resolved {
+            println!("{}={}", r.role, r.path);
+        }
+    }
+
+    Ok(())
+}
+
+/// 每次有设备事件就重新扫描，直到所有角色都能解析 (顺序与 `roles` 相同)
+/// `events` 需要在调用前就开始监听；`timeout` 为 None 时一直等待，超时报错并列出仍然缺失的角色
+pub(super) fn wait_resolved(
+    rules: &[DeviceConfig],
+    roles: &[String],
+    events: &Receiver<DeviceEvent>,
+    timeout: Option<Duration>,
+) -> Result<Vec<ResolvedRole>> {
+    // 超时时间过大 (加法溢出) 时视为不限时
+    let deadline = timeout.and_then(|t| Instant::now().checked_add(t));
+
+    loop {
+        let raw_devices = manager::scan_once()?;
+        let resolved: Vec<_> = roles
+            .iter()
+            .filter_map(|role| service::resolve_role(&raw_devices, rules, role))
+            .collect();
+
+        if resolved.len() == roles.len() {
+            return Ok(resolved);
+        }
+
+        // 等待下一次设备变化 (事件可能成批到达，合并后只扫描一次)
+        let received = match deadline {
+            Some(deadline) => events
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .is_ok(),
+            None => events.recv().is_ok(),
+        };
+        if !received {
+            let missing: Vec<&str> = roles
+                .iter()
+                .filter(|role| !resolved.iter().any(|r| &r.role == *role))
+                .map(String::as_str)
+                .collect();
+            if let (Some(timeout), Some(_)) = (timeout, deadline) {
+                bail!(
+                    "timed out after {}; still missing: {}",
+                    humantime::format_duration(timeout),
+                    missing.join(", ")
+                );
+            }
+            bail!(
+                "device monitor stopped; still missing: {}",
+                missing.join(", ")
+            );
+        }
+        while events.try_recv().is_ok() {}
+    }
+}
+
+/// `doratool resolve <role>`
+/// 只输出路径 (优先 /dev 节点)，方便在 shell 脚本里 `$(doratool resolve top_camera)`
+pub fn resolve(role: &str) -> Result<()> {
+    let paths = AppPaths::new()?;
+    let rules = config::load_rules(&paths.config_file)?;
+    let raw_devices = manager::scan_once()?;
+
+    if let Some(resolved) = service::resolve_role(&raw_devices, &rules, role) {
+        println!("{}", resolved.path);
+        return Ok(());
+    }
+
+    let conflict = service::detect_conflicts(&raw_devices, &rules)
+        .into_iter()
+        .find(|c| c.involves_role(role));
+    match conflict {
+        Some(conflict) => bail!("role '{}' is ambiguous: {}", role, conflict),
+        None => bail!("role '{}' is not bound to any connected device", role),
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    os::unix::process::{CommandExt, ExitStatusExt},
+    process::{Child, Command, ExitStatus},
+    sync::atomic::{AtomicBool, AtomicI32, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+use crossbeam_channel::{Receiver, RecvTimeoutError, unbounded};
+use nix::{
+    sys::signal::{self, SigHandler, Signal, kill},
+    unistd::Pid,
+};
+use usb_resolver::{DeviceEvent, get_monitor};
+
+use crate::{
+    cli::devices,
+    core::usb::{
+        env, manager,
+        models::{DeviceConfig, ResolvedRole},
+        service,
+    },
+    infra::config::{self, AppPaths},
+};
+
+// 检查子进程是否退出的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+// 重启时等待子进程响应 SIGTERM 的时间，超时后 SIGKILL
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+// 设备事件往往成批到达 (同一个设备的多个接口、Hub 下的多个设备)，安静这么久之后才扫描
+const DEBOUNCE: Duration = Duration::from_millis(300);
+// 合并一批事件最多等待的时间，避免持续不断的事件让扫描一直推迟
+const DEBOUNCE_MAX: Duration = Duration::from_millis(1500);
+// 子进程自己退出后，等待设备事件的时间：
+// 拔出设备时子进程往往先因为 EIO / ENODEV 退出，这时 udev 可能还没处理完 remove 事件，立即扫描会看到旧路径
+const EXIT_SETTLE: Duration = Duration::from_millis(1500);
+// 转发给子进程的信号
+const FORWARDED_SIGNALS: [Signal; 3] = [Signal::SIGTERM, Signal::SIGINT, Signal::SIGHUP];
+
+// 当前子进程的 pid (0 表示没有子进程)，以及是否已经转发过信号，供信号处理函数使用
+static CHILD_PID: AtomicI32 = AtomicI32::new(0);
+static FORWARDED: AtomicBool = AtomicBool::new(false);
+
+/// `doratool exec --role cam=top_camera [--restart] -- my_node --device {cam}`
+/// 解析角色后启动子进程：参数和 `--env` 中的 `{alias}` 替换为设备路径，并设置 `DORA_ROLE_*` 环境变量。
+/// 不带 `--restart` 时直接用子命令替换当前进程 (exec)，信号和退出码都由它自己处理；
+/// `--restart` 时角色的设备被拔出 (或路径变化) 会停止子进程，重新插入后再启动，
+/// 收到的 SIGTERM / SIGINT / SIGHUP 转发给子进程，并以子进程的退出码退出
+pub fn exec(
+    roles: &[(String, String)],
+    envs: &[(String, String)],
+    timeout: Duration,
+    restart: bool,
+    command: &[String],
+) -> Result<()> {
+    let paths = AppPaths::new()?;
+    let rules = config::load_rules(&paths.config_file)?;
+
+    for (i, (alias, role)) in roles.iter().enumerate() {
+        if !rules.iter().any(|r| &r.role == role) {
+            bail!("role '{}' is not configured", role);
+        }
+        if roles[..i].iter().any(|(a, _)| a == alias) {
+            bail!("alias '{}' is used more than once", alias);
+        }
+    }
+    // 同一个角色可以有多个别名，但只解析一次 (也只生成一个 DORA_ROLE_* 变量)
+    let mut role_names: Vec<String> = Vec::new();
+    for (_, role) in roles {
+        if !role_names.contains(role) {
+            role_names.push(role.clone());
+        }
+    }
+
+    let (tx, rx) = unbounded();
+    // 先开始监听再扫描，避免扫描之后插入的设备被漏掉
+    get_monitor().start(tx)?;
+
+    let mut resolved = devices::wait_resolved(&rules, &role_names, &rx, Some(timeout))?;
+    if !restart {
+        // exec 只在失败时返回
+        let err = build_command(command, roles, envs, &resolved)?.exec();
+        return Err(err).with_context(|| format!("failed to start '{}'", command[0]));
+    }
+
+    forward_signals()?;
+    loop {
+        let mut child = build_command(command, roles, envs, &resolved)?
+            .spawn()
+            .with_context(|| format!("failed to start '{}'", command[0]))?;
+        CHILD_PID.store(child.id() as i32, Ordering::SeqCst);
+
+        // 等待子进程退出，或者角色对应的设备发生变化
+        let changed = loop {
+            let exited = child.try_wait()?;
+            let forwarded = FORWARDED.load(Ordering::SeqCst);
+            match exited {
+                None => match rx.recv_timeout(POLL_INTERVAL) {
+                    Ok(_) => debounce(&rx),
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    // 设备监听已经停止，无法再重启，只能等子进程自己退出
+                    Err(RecvTimeoutError::Disconnected) => exit_with(child.wait()?),
+                },
+                // 子进程可能因为设备被拔出而先退出，等设备事件到达后再判断
+                Some(_) if !forwarded => {
+                    if rx.recv_timeout(EXIT_SETTLE).is_ok() {
+                        debounce(&rx);
+                    }
+                }
+                Some(_) => {}
+            }
+
+            let changed = if exited.is_some() && forwarded {
+                Vec::new()
+            } else {
+                changed_roles(&rules, &resolved)?
+            };
+            match next_step(exited, forwarded, changed) {
+                Next::Keep => {}
+                Next::Restart(changed) => break changed,
+                Next::Exit(status) => exit_with(status),
+            }
+        };
+
+        eprintln!(
+            "device changed for {}, restarting '{}'",
+            changed.join(", "),
+            command[0]
+        );
+        stop(&mut child)?;
+        CHILD_PID.store(0, Ordering::SeqCst);
+        resolved = devices::wait_resolved(&rules, &role_names, &rx, None)?;
+    }
+}
+
+/// 子进程状态或设备变化之后的下一步
+#[derive(Debug, PartialEq, Eq)]
+enum Next {
+    // 继续运行
+    Keep,
+    // 这些角色离线或路径变化，停止子进程，等设备重新就绪后再启动
+    Restart(Vec<String>),
+    // 以子进程的退出码退出
+    Exit(ExitStatus),
+}
+
+// 转发过终止信号后子进程退出，不再重启；
+// 否则角色有变化就重启 (包括子进程因为设备被拔出而先退出)，子进程自己正常退出时跟着退出
+fn next_step(exited: Option<ExitStatus>, forwarded: bool, changed: Vec<String>) -> Next {
+    match exited {
+        Some(status) if forwarded => Next::Exit(status),
+        _ if !changed.is_empty() => Next::Restart(changed),
+        Some(status) => Next::Exit(status),
+        None => Next::Keep,
+    }
+}
+
+// 收到一个设备事件后，继续接收直到安静 `DEBOUNCE` (最多 `DEBOUNCE_MAX`)，之后只扫描一次
+fn debounce(rx: &Receiver<DeviceEvent>) {
+    let deadline = Instant::now() + DEBOUNCE_MAX;
+    while Instant::now() < deadline && rx.recv_timeout(DEBOUNCE).is_ok() {}
+}
+
+// 替换参数中的 `{alias}`，构造子进程的命令
+fn build_command(
+    command: &[String],
+    roles: &[(String, String)],
+    envs: &[(String, String)],
+    resolved: &[ResolvedRole],
+) -> Result<Command> {
+    let aliases: Vec<(&str, &str)> = roles
+        .iter()
+        .filter_map(|(alias, role)| {
+            let r = resolved.iter().find(|r| &r.role == role)?;
+            Some((alias.as_str(), r.path.as_str()))
+        })
+        .collect();
+
+    let args: Vec<String> = command
+        .iter()
+        .map(|arg| substitute(arg, &aliases))
+        .collect();
+    let vars = env::env_vars(resolved)?;
+
+    let mut cmd = Command::new(&args[0]);
+    cmd.args(&args[1..])
+        .envs(vars)
+        .envs(envs.iter().map(|(k, v)| (k, substitute(v, &aliases))));
+    Ok(cmd)
+}
+
+// 安装信号处理函数：有子进程时把信号转发给它，没有时 (例如正在等待设备) 按默认行为退出
+fn forward_signals() -> Result<()> {
+    for sig in FORWARDED_SIGNALS {
+        // SAFETY: 处理函数只访问原子变量，并调用 kill / sigaction / raise 这些异步信号安全的函数
+        unsafe { signal::signal(sig, SigHandler::Handler(on_signal)) }
+            .with_context(|| format!("failed to install {} handler", sig))?;
+    }
+    Ok(())
+}
+
+extern "C" fn on_signal(raw: i32) {
+    let Ok(sig) = Signal::try_from(raw) else {
+        return;
+    };
+    let pid = CHILD_PID.load(Ordering::SeqCst);
+    if pid > 0 {
+        FORWARDED.store(true, Ordering::SeqCst);
+        let _ = kill(Pid::from_raw(pid), sig);
+    } else {
+        // SAFETY: 恢复默认处理后重新发送给自己
+        let _ = unsafe { signal::signal(sig, SigHandler::SigDfl) };
+        let _ = signal::raise(sig);
+    }
+}
+
+fn substitute(s: &str, aliases: &[(&str, &str)]) -> String {
+    aliases.iter().fold(s.to_string(), |s, (alias, path)| {
+        s.replace(&format!("{{{}}}", alias), path)
+    })
+}
+
+// 重新扫描，返回已经离线或者路径发生变化的角色
+fn changed_roles(rules: &[DeviceConfig], resolved: &[ResolvedRole]) -> Result<Vec<String>> {
+    let raw_devices = manager::scan_once()?;
+    Ok(resolved
+        .iter()
+        .filter(|r| {
+            service::resolve_role(&raw_devices, rules, &r.role).map(|now| now.path)
+                != Some(r.path.clone())
+        })
+        .map(|r| r.role.clone())
+        .collect())
+}
+
+// 先 SIGTERM，超时后 SIGKILL
+fn stop(child: &mut Child) -> Result<()> {
+    if child.try_wait()?.is_some() {
+        return Ok(());
+    }
+
+    kill(Pid::from_raw(child.id() as i32), Signal::SIGTERM)
+        .context("failed to send SIGTERM to child")?;
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while Instant::now() < deadline {
+        if child.try_wait()?.is_some() {
+            return Ok(());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
+
+// 以子进程的退出码退出；被信号终止时按 shell 的惯例返回 128 + 信号值
+fn exit_with(status: ExitStatus) -> ! {
+    let code = status
+        .code()
+        .unwrap_or_else(|| 128 + status.signal().unwrap_or(0));
+    std::process::exit(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(code: i32) -> ExitStatus {
+        ExitStatus::from_raw(code << 8)
+    }
+
+    fn roles(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_running_while_nothing_changed() {
+        assert_eq!(next_step(None, false, Vec::new()), Next::Keep);
+    }
+
+    #[test]
+    fn restarts_when_a_role_changed() {
+        assert_eq!(
+            next_step(None, false, roles(&["cam"])),
+            Next::Restart(roles(&["cam"]))
+        );
+    }
+
+    #[test]
+    fn restarts_when_child_died_because_its_device_went_away() {
+        // 子进程因为设备被拔出 (EIO) 先退出，不能就此结束
+        assert_eq!(
+            next_step(Some(status(1)), false, roles(&["cam"])),
+            Next::Restart(roles(&["cam"]))
+        );
+    }
+
+    #[test]
+    fn exits_with_child_status_when_devices_are_unchanged() {
+        assert_eq!(
+            next_step(Some(status(3)), false, Vec::new()),
+            Next::Exit(status(3))
+        );
+    }
+
+    #[test]
+    fn exits_after_forwarded_signal_even_if_devices_changed() {
+        assert_eq!(
+            next_step(Some(status(0)), true, roles(&["cam"])),
+            Next::Exit(status(0))
+        );
+        // 子进程还没退出时继续等它
+        assert_eq!(next_step(None, true, Vec::new()), Next::Keep);
+    }
+}
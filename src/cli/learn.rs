@@ -0,0 +1,87 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+
+use crate::{
+    core::usb::{
+        diff::DeviceKey,
+        learn::{self, Learned},
+        manager,
+        models::BindStrategy,
+        service,
+    },
+    infra::{
+        config::{self, AppPaths},
+        settings::Settings,
+    },
+};
+
+/// `doratool learn <role>`
+/// 本地轮询扫描 (不依赖正在运行的 Web 服务)，把下一个新插入的设备绑定到角色
+/// 期间拔出一个已绑定的设备会输出它的角色并退出 (不修改规则)
+/// 设备的挑选规则与服务端相同 (见 `learn::pick_device`)
+pub fn learn(role: &str, strategy: Option<BindStrategy>, timeout: Duration) -> Result<()> {
+    let paths = AppPaths::new()?;
+    let settings = Settings::load(&paths.settings_file, Default::default())?;
+    let mut rules = config::load_rules(&paths.config_file)?;
+
+    if rules.iter().any(|r| r.role == role) {
+        bail!("role '{}' already exists", role);
+    }
+
+    let baseline = manager::scan_once()?;
+    eprintln!(
+        "waiting for a device to be plugged in ({}s, unplug a bound device to identify it)...",
+        timeout.as_secs()
+    );
+
+    // 超时过大时不设截止时间
+    let deadline = Instant::now().checked_add(timeout);
+    while deadline.is_none_or(|d| Instant::now() < d) {
+        thread::sleep(settings.scan_interval());
+        let current = manager::scan_once()?;
+
+        let (raw, current) = match learn::pick_device(&baseline, &current, &rules) {
+            None => continue,
+            Some(Learned::Detached { role, device }) => {
+                println!(
+                    "unplugged device {} is role '{}'",
+                    DeviceKey::of(&device),
+                    role
+                );
+                return Ok(());
+            }
+            Some(Learned::Attached(_)) => {
+                // 等待 Hub 下面的设备枚举完成后重新挑选
+                thread::sleep(learn::SETTLE_WINDOW);
+                let current = manager::scan_once()?;
+                match learn::pick_device(&baseline, &current, &rules) {
+                    Some(Learned::Attached(raw)) => (raw, current),
+                    _ => continue,
+                }
+            }
+        };
+
+        if let Some(existing) = service::match_rule(&raw, &rules, &current) {
+            bail!(
+                "device {} is already bound to role '{}'",
+                DeviceKey::of(&raw),
+                existing.role
+            );
+        }
+
+        let rule = service::rule_for_device(&raw, &current, role, strategy)?;
+        let strategy = rule.strategy;
+        rules.push(rule);
+        service::validate_rules(&rules)?;
+        config::save_rules(&paths.config_file, &rules)?;
+
+        println!("bound '{}' to {} ({})", role, DeviceKey::of(&raw), strategy);
+        return Ok(());
+    }
+
+    bail!("no device was plugged in within {}s", timeout.as_secs())
+}
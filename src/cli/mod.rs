@@ -1 +1,57 @@
 pub mod commands;
+pub mod daemon;
+pub mod dataflow;
+pub mod devices;
+pub mod exec;
+pub mod learn;
+pub mod rules;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::{
+    cli::commands::{Cli, Commands, ServeArgs},
+    infra::{config::AppPaths, settings::Settings},
+};
+
+/// 解析命令行并分发到对应的子命令
+/// 注意：这里是同步函数，只有 `serve` 才会创建 tokio runtime
+pub fn run() -> Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command.unwrap_or(Commands::Serve(ServeArgs::default())) {
+        Commands::Serve(args) => {
+            let paths = AppPaths::new()?;
+            let settings = Settings::load(&paths.settings_file, args.into())?;
+            serve(paths, settings, || {})
+        }
+        Commands::List { json } => devices::list(json),
+        Commands::Explain { device_id, json } => devices::explain(&device_id, json),
+        Commands::Resolve { role } => devices::resolve(&role),
+        Commands::Check { roles, json } => devices::check(&roles, json),
+        Commands::Env { format } => devices::env(format),
+        Commands::WaitFor { roles, timeout } => devices::wait_for(&roles, timeout),
+        Commands::Exec {
+            roles,
+            envs,
+            timeout,
+            restart,
+            command,
+        } => exec::exec(&roles, &envs, timeout, restart, &command),
+        Commands::Learn {
+            role,
+            strategy,
+            timeout,
+        } => learn::learn(&role, strategy, Duration::from_secs(timeout)),
+        Commands::Rules { action } => rules::execute(action),
+        Commands::Dataflow { action } => dataflow::execute(action),
+        Commands::Daemon { action } => daemon::execute(action),
+    }
+}
+
+/// 创建 tokio runtime 并启动服务 (阻塞直到收到退出信号)
+fn serve(paths: AppPaths, settings: Settings, on_ready: impl FnOnce()) -> Result<()> {
+    tokio::runtime::Runtime::new()?.block_on(crate::run(paths, settings, on_ready))
+}
@@ -0,0 +1,138 @@
+use anyhow::{Result, bail};
+
+use crate::{
+    cli::commands::{RuleArgs, RulesCommand},
+    core::usb::{
+        manager,
+        models::{BindStrategy, DeviceConfig, TopologyAnchor},
+        service,
+    },
+    infra::config::{self, AppPaths},
+};
+
+pub fn execute(action: RulesCommand) -> Result<()> {
+    let paths = AppPaths::new()?;
+
+    match action {
+        RulesCommand::Add(args) => add(&paths, args),
+        RulesCommand::Bind {
+            device_id,
+            role,
+            strategy,
+        } => bind(&paths, &device_id, &role, strategy),
+        RulesCommand::Remove { role } => remove(&paths, &role),
+        RulesCommand::Show { json } => show(&paths, json),
+    }
+}
+
+/// `doratool rules add`
+fn add(paths: &AppPaths, args: RuleArgs) -> Result<()> {
+    let mut rules = config::load_rules(&paths.config_file)?;
+
+    if rules.iter().any(|r| r.role == args.role) {
+        bail!("role '{}' already exists", args.role);
+    }
+
+    let topology = args.anchor.map(|root| TopologyAnchor {
+        root,
+        chain: args.chain.unwrap_or_default(),
+    });
+    let strategy = args.strategy.unwrap_or_else(|| match topology {
+        Some(_) => BindStrategy::Topology,
+        None => BindStrategy::infer(args.serial.as_deref(), args.port_path.as_deref()),
+    });
+    rules.push(DeviceConfig {
+        role: args.role.clone(),
+        vid: args.vid,
+        pid: args.pid,
+        strategy,
+        serial: args.serial,
+        port_path: args.port_path,
+        topology,
+        priority: args.priority,
+        required: args.required,
+    });
+    service::validate_rules(&rules)?;
+    config::save_rules(&paths.config_file, &rules)?;
+
+    println!("added rule '{}'", args.role);
+    Ok(())
+}
+
+/// `doratool rules bind`
+/// 本地扫描一次，按设备 id 生成规则 (与 `POST /api/devices/{id}/bind` 相同的逻辑)
+fn bind(
+    paths: &AppPaths,
+    device_id: &str,
+    role: &str,
+    strategy: Option<BindStrategy>,
+) -> Result<()> {
+    let mut rules = config::load_rules(&paths.config_file)?;
+
+    if rules.iter().any(|r| r.role == role) {
+        bail!("role '{}' already exists", role);
+    }
+
+    let raw_devices = manager::scan_once()?;
+    let Some(raw) = service::find_device(&raw_devices, device_id) else {
+        bail!("device '{}' not found", device_id);
+    };
+
+    let rule = service::rule_for_device(raw, &raw_devices, role, strategy)?;
+    let strategy = rule.strategy;
+    rules.push(rule);
+    service::validate_rules(&rules)?;
+    config::save_rules(&paths.config_file, &rules)?;
+
+    println!("bound '{}' to {} ({})", role, device_id, strategy);
+    Ok(())
+}
+
+/// `doratool rules remove`
+fn remove(paths: &AppPaths, role: &str) -> Result<()> {
+    let mut rules = config::load_rules(&paths.config_file)?;
+
+    let before_len = rules.len();
+    rules.retain(|r| r.role != role);
+    if rules.len() == before_len {
+        bail!("role '{}' not found", role);
+    }
+    config::save_rules(&paths.config_file, &rules)?;
+
+    println!("removed rule '{}'", role);
+    Ok(())
+}
+
+/// `doratool rules show`
+fn show(paths: &AppPaths, json: bool) -> Result<()> {
+    let rules = config::load_rules(&paths.config_file)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rules)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<20} {:<8} {:<8} {:<8} {:<12} {:<8} {:<20} PORT_PATH",
+        "ROLE", "REQUIRED", "VID", "PID", "STRATEGY", "PRIORITY", "SERIAL"
+    );
+    for rule in &rules {
+        println!(
+            "{:<20} {:<8} 0x{:04x}   0x{:04x}   {:<12} {:<8} {:<20} {}",
+            rule.role,
+            if rule.required { "yes" } else { "no" },
+            rule.vid,
+            rule.pid,
+            rule.strategy.to_string(),
+            rule.priority,
+            rule.serial.as_deref().unwrap_or("-"),
+            // 拓扑规则没有 port_path，这一列显示锚点
+            match &rule.topology {
+                Some(anchor) => anchor.to_string(),
+                None => rule.port_path.clone().unwrap_or_else(|| "-".to_string()),
+            }
+        );
+    }
+
+    Ok(())
+}
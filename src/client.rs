@@ -0,0 +1,137 @@
+//! Lightweight blocking client for the local doratool server
+//! Lets Rust dora nodes ask "which `/dev/...` is role `left_arm` right now?"
+// 本地 doratool 服务的轻量阻塞客户端
+// 供 Rust 的 dora 节点查询 "角色 left_arm 现在对应哪个 /dev/..."
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, de::DeserializeOwned};
+
+pub use crate::core::usb::models::{RawDevice, ResolvedRole};
+
+/// 默认服务地址，可通过环境变量 `DORATOOL_URL` 覆盖
+pub const DEFAULT_BASE_URL: &str = "http://127.0.0.1:3000";
+/// 默认请求超时
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 服务端统一响应结构 (见 `server::response::ApiResponse`)
+#[derive(Debug, Deserialize)]
+struct Envelope<T> {
+    code: i32,
+    msg: String,
+    data: Option<T>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: String,
+    timeout: Duration,
+    agent: ureq::Agent,
+}
+
+impl Default for Client {
+    /// `DORATOOL_URL` 或默认地址，默认超时
+    fn default() -> Self {
+        let base_url =
+            std::env::var("DORATOOL_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+        Self::new(base_url)
+    }
+}
+
+impl Client {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self {
+            agent: build_agent(DEFAULT_TIMEOUT),
+            timeout: DEFAULT_TIMEOUT,
+            base_url,
+        }
+    }
+
+    /// 设置单次请求的总超时 (包括连接和读取)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.agent = build_agent(timeout);
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// `GET /api/roles/{role}/resolve`
+    pub fn resolve_role(&self, role: &str) -> Result<ResolvedRole> {
+        self.get(&format!("/api/roles/{}/resolve", encode_path_segment(role)))
+            .with_context(|| format!("failed to resolve role '{}'", role))
+    }
+
+    /// `GET /api/roles/{role}/wait?timeout=`
+    /// 阻塞直到角色上线或超时 (请求超时会在 `wait` 的基础上自动放宽)
+    pub fn wait_for_role(&self, role: &str, wait: Duration) -> Result<ResolvedRole> {
+        let path = format!(
+            "/api/roles/{}/wait?timeout={}ms",
+            encode_path_segment(role),
+            wait.as_millis()
+        );
+        let agent = build_agent(wait.saturating_add(self.timeout));
+        self.get_with(&agent, &path)
+            .with_context(|| format!("failed to wait for role '{}'", role))
+    }
+
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.get_with(&self.agent, path)
+    }
+
+    fn get_with<T: DeserializeOwned>(&self, agent: &ureq::Agent, path: &str) -> Result<T> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut response = agent
+            .get(&url)
+            .call()
+            .with_context(|| format!("request to {} failed", url))?;
+
+        let envelope: Envelope<T> = response
+            .body_mut()
+            .read_json()
+            .with_context(|| format!("invalid response from {}", url))?;
+
+        match envelope {
+            Envelope {
+                code: 0,
+                data: Some(data),
+                ..
+            } => Ok(data),
+            Envelope { code, msg, .. } => bail!("server returned {} ({})", code, msg),
+        }
+    }
+}
+
+/// 用默认客户端 (`DORATOOL_URL` 或 127.0.0.1:3000) 解析角色
+pub fn resolve_role(role: &str) -> Result<ResolvedRole> {
+    Client::default().resolve_role(role)
+}
+
+fn build_agent(timeout: Duration) -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(timeout))
+        // 错误状态码也要读 body 里的 code/msg
+        .http_status_as_error(false)
+        .build()
+        .into()
+}
+
+// 路径参数的百分号编码 (只保留 RFC 3986 的非保留字符)
+fn encode_path_segment(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -0,0 +1,233 @@
+//! dora 数据流 (`dataflow.yml`) 中的角色占位符：`${role:top_camera}` -> 当前解析出的设备路径
+//! 直接按文本替换，不解析 YAML，保留原文件的注释和格式；`#` 注释里的占位符会被忽略 (原样保留)
+
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+
+use crate::core::usb::models::ResolvedRole;
+
+const PLACEHOLDER_START: &str = "${role:";
+
+/// 数据流中引用的一个角色，以及它出现的行号 (从 1 开始)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleReference {
+    pub role: String,
+    pub lines: Vec<usize>,
+}
+
+// 一个占位符：在文本中的字节范围、角色名
+struct Placeholder<'a> {
+    start: usize,
+    end: usize,
+    role: &'a str,
+}
+
+/// 数据流引用的所有角色，按首次出现的顺序
+/// 占位符没有闭合或者角色名为空时报错
+pub fn referenced_roles(text: &str) -> Result<Vec<RoleReference>> {
+    let mut references: Vec<RoleReference> = Vec::new();
+    for placeholder in placeholders(text)? {
+        let line = line_of(text, placeholder.start);
+        match references.iter_mut().find(|r| r.role == placeholder.role) {
+            Some(reference) => {
+                if !reference.lines.contains(&line) {
+                    reference.lines.push(line);
+                }
+            }
+            None => references.push(RoleReference {
+                role: placeholder.role.to_string(),
+                lines: vec![line],
+            }),
+        }
+    }
+    Ok(references)
+}
+
+/// 把所有占位符替换成角色的路径
+/// 调用方应先用 `referenced_roles` 检查角色都已解析；仍有未解析的角色时报错
+pub fn render(text: &str, resolved: &[ResolvedRole]) -> Result<String> {
+    let paths: HashMap<&str, &str> = resolved
+        .iter()
+        .map(|r| (r.role.as_str(), r.path.as_str()))
+        .collect();
+
+    let mut output = String::with_capacity(text.len());
+    let mut last = 0;
+    for placeholder in placeholders(text)? {
+        let Some(path) = paths.get(placeholder.role) else {
+            bail!(
+                "role '{}' (line {}) is not resolved",
+                placeholder.role,
+                line_of(text, placeholder.start)
+            );
+        };
+        output.push_str(&text[last..placeholder.start]);
+        output.push_str(path);
+        last = placeholder.end;
+    }
+    output.push_str(&text[last..]);
+
+    Ok(output)
+}
+
+fn placeholders(text: &str) -> Result<Vec<Placeholder<'_>>> {
+    let mut found = Vec::new();
+    // 角色名不能跨行，逐行扫描，避免一个没闭合的占位符吞掉后面的内容
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let code = &line[..comment_start(line)];
+        let mut offset = 0;
+        while let Some(index) = code[offset..].find(PLACEHOLDER_START) {
+            let start = line_start + offset + index;
+            let name_start = offset + index + PLACEHOLDER_START.len();
+            let Some(len) = code[name_start..].find('}') else {
+                bail!("unclosed placeholder at line {}", line_of(text, start));
+            };
+            let name_end = name_start + len;
+
+            let role = code[name_start..name_end].trim();
+            if role.is_empty() {
+                bail!("empty role name at line {}", line_of(text, start));
+            }
+            found.push(Placeholder {
+                start,
+                end: line_start + name_end + 1,
+                role,
+            });
+            offset = name_end + 1;
+        }
+        line_start += line.len();
+    }
+    Ok(found)
+}
+
+// 行内 YAML 注释的起始位置 (没有注释时为行尾)
+// `#` 位于行首或空白之后才是注释；引号字符串 ('...' / "...") 里的 `#` 不算
+fn comment_start(line: &str) -> usize {
+    let mut quote: Option<char> = None;
+    let mut prev: Option<char> = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            // 双引号内的转义字符，单引号内 '' 表示一个单引号
+            Some('"') if c == '\\' => {
+                chars.next();
+            }
+            Some('\'') if c == '\'' && chars.peek().is_some_and(|&(_, next)| next == '\'') => {
+                chars.next();
+            }
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '#' && prev.is_none_or(char::is_whitespace) => return i,
+            // 引号只在值的开头才开始一个字符串 (`it's` 里的单引号不算)
+            None if (c == '\'' || c == '"')
+                && prev.is_none_or(|p| p.is_whitespace() || "[{,:-".contains(p)) =>
+            {
+                quote = Some(c)
+            }
+            None => {}
+        }
+        prev = Some(c);
+    }
+    line.len()
+}
+
+fn line_of(text: &str, index: usize) -> usize {
+    text[..index].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::usb::models::RawDevice;
+
+    fn resolved(role: &str, path: &str) -> ResolvedRole {
+        ResolvedRole {
+            role: role.to_string(),
+            path: path.to_string(),
+            system_path: String::new(),
+            device: RawDevice {
+                vid: 0x1234,
+                pid: 0x5678,
+                serial: None,
+                port_path: "N/A".to_string(),
+                system_path: String::new(),
+                system_path_alt: None,
+            },
+        }
+    }
+
+    fn roles(text: &str) -> Vec<(String, Vec<usize>)> {
+        referenced_roles(text)
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.role, r.lines))
+            .collect()
+    }
+
+    #[test]
+    fn collects_roles_in_order_with_lines() {
+        let text = "nodes:\n  - id: cam\n    args: ${role:top} ${role: arm }\n    env:\n      DEV: ${role:top}\n";
+        assert_eq!(
+            roles(text),
+            vec![
+                ("top".to_string(), vec![3, 5]),
+                ("arm".to_string(), vec![3])
+            ]
+        );
+        assert!(referenced_roles("nodes: []\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_unclosed_and_empty_placeholders() {
+        let err = referenced_roles("a: 1\nb: ${role:cam\n").unwrap_err();
+        assert_eq!(err.to_string(), "unclosed placeholder at line 2");
+
+        // 占位符不能跨行
+        let err = referenced_roles("b: ${role:\n  cam}\n").unwrap_err();
+        assert_eq!(err.to_string(), "unclosed placeholder at line 1");
+
+        let err = referenced_roles("a: ${role:}\n").unwrap_err();
+        assert_eq!(err.to_string(), "empty role name at line 1");
+        let err = referenced_roles("a: ${role:  }").unwrap_err();
+        assert_eq!(err.to_string(), "empty role name at line 1");
+    }
+
+    #[test]
+    fn ignores_placeholders_in_comments() {
+        let text = "# ${role:old}\nargs: ${role:cam} # was ${role:old\nurl: a#${role:x}\n";
+        let names: Vec<String> = roles(text).into_iter().map(|(r, _)| r).collect();
+        assert_eq!(names, ["cam", "x"]);
+    }
+
+    #[test]
+    fn hash_inside_quotes_is_not_a_comment() {
+        let text = "a: \"x # ${role:a}\"\nb: 'it''s # ${role:b}'\nc: it's # ${role:c}\nd: \"\\\" # ${role:d}\"\n";
+        let names: Vec<String> = roles(text).into_iter().map(|(r, _)| r).collect();
+        assert_eq!(names, ["a", "b", "d"]);
+    }
+
+    #[test]
+    fn render_replaces_placeholders_and_keeps_comments() {
+        let text = "# ${role:old}\nnodes:\n  - args: --dev ${role:cam} --arm=${role:arm}\n    env:\n      CAM: ${role:cam}";
+        let rendered = render(
+            text,
+            &[
+                resolved("cam", "/dev/video0"),
+                resolved("arm", "/dev/ttyUSB0"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "# ${role:old}\nnodes:\n  - args: --dev /dev/video0 --arm=/dev/ttyUSB0\n    env:\n      CAM: /dev/video0"
+        );
+    }
+
+    #[test]
+    fn render_fails_on_unresolved_role() {
+        let err = render("a: 1\nb: ${role:cam}\n", &[]).unwrap_err();
+        assert_eq!(err.to_string(), "role 'cam' (line 2) is not resolved");
+    }
+}
@@ -0,0 +1,196 @@
+use std::{collections::HashMap, fmt};
+
+use usb_resolver::RawDeviceInfo;
+
+/// Stable identity of a physical device: VID/PID + the physical port it is plugged into
+/// Falls back to `system_path` when the platform cannot report a port path ("N/A").
+// 设备的稳定标识：VID/PID + 物理端口
+// 平台拿不到端口路径 ("N/A") 时退化为 system_path
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeviceKey {
+    pub vid: u16,
+    pub pid: u16,
+    pub location: String,
+}
+
+impl DeviceKey {
+    pub fn of(raw: &RawDeviceInfo) -> Self {
+        let location = if raw.port_path.is_empty() || raw.port_path == "N/A" {
+            raw.system_path.clone()
+        } else {
+            raw.port_path.clone()
+        };
+
+        Self {
+            vid: raw.vid,
+            pid: raw.pid,
+            location,
+        }
+    }
+}
+
+impl fmt::Display for DeviceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x}:{:04x}@{}", self.vid, self.pid, self.location)
+    }
+}
+
+/// 两次扫描之间的一条变更
+#[derive(Debug, Clone)]
+pub enum DeviceChange {
+    // 新插入的设备
+    Added(RawDeviceInfo),
+    // 被拔出的设备 (保存拔出前的信息)
+    Removed(RawDeviceInfo),
+    // 同一个设备，但序列号/系统路径等发生了变化 (例如 tty 节点晚于 usb_device 出现)
+    Changed {
+        before: RawDeviceInfo,
+        after: RawDeviceInfo,
+    },
+}
+
+impl DeviceChange {
+    pub fn key(&self) -> DeviceKey {
+        match self {
+            Self::Added(raw) | Self::Removed(raw) => DeviceKey::of(raw),
+            Self::Changed { after, .. } => DeviceKey::of(after),
+        }
+    }
+}
+
+/// 对比上一次快照和本次扫描结果，生成增量变更
+/// 纯内存计算，不做任何 IO
+pub fn diff_devices(previous: &[RawDeviceInfo], current: &[RawDeviceInfo]) -> Vec<DeviceChange> {
+    let previous_by_key: HashMap<DeviceKey, &RawDeviceInfo> =
+        previous.iter().map(|d| (DeviceKey::of(d), d)).collect();
+    let current_by_key: HashMap<DeviceKey, &RawDeviceInfo> =
+        current.iter().map(|d| (DeviceKey::of(d), d)).collect();
+
+    let mut changes = Vec::new();
+
+    // 保持扫描顺序，方便日志阅读
+    for dev in current {
+        match previous_by_key.get(&DeviceKey::of(dev)) {
+            None => changes.push(DeviceChange::Added(dev.clone())),
+            Some(before) if !same_device_info(before, dev) => changes.push(DeviceChange::Changed {
+                before: (*before).clone(),
+                after: dev.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    for dev in previous {
+        if !current_by_key.contains_key(&DeviceKey::of(dev)) {
+            changes.push(DeviceChange::Removed(dev.clone()));
+        }
+    }
+
+    changes
+}
+
+/// 把变更应用到设备列表上 (只修改有变化的条目)
+pub fn apply_changes(devices: &mut Vec<RawDeviceInfo>, changes: &[DeviceChange]) {
+    for change in changes {
+        let key = change.key();
+        let position = devices.iter().position(|d| DeviceKey::of(d) == key);
+
+        match (change, position) {
+            (DeviceChange::Removed(_), Some(index)) => {
+                devices.remove(index);
+            }
+            (DeviceChange::Removed(_), None) => {}
+            (DeviceChange::Added(raw) | DeviceChange::Changed { after: raw, .. }, Some(index)) => {
+                devices[index] = raw.clone();
+            }
+            (DeviceChange::Added(raw) | DeviceChange::Changed { after: raw, .. }, None) => {
+                devices.push(raw.clone());
+            }
+        }
+    }
+}
+
+/// RawDeviceInfo 没有实现 PartialEq，这里逐字段比较 (标识字段之外的部分)
+fn same_device_info(a: &RawDeviceInfo, b: &RawDeviceInfo) -> bool {
+    a.serial == b.serial
+        && a.port_path == b.port_path
+        && a.system_path == b.system_path
+        && a.system_path_alt == b.system_path_alt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(pid: u16, port_path: &str, system_path: &str) -> RawDeviceInfo {
+        RawDeviceInfo {
+            vid: 0x1234,
+            pid,
+            serial: None,
+            port_path: port_path.to_string(),
+            system_path: system_path.to_string(),
+            system_path_alt: None,
+        }
+    }
+
+    #[test]
+    fn detects_added_removed_and_changed_devices() {
+        let kept = device(1, "pci-0000:00:14.0-usb-0:1:1.0", "/sys/a");
+        let removed = device(2, "pci-0000:00:14.0-usb-0:2:1.0", "/sys/b");
+        let added = device(3, "pci-0000:00:14.0-usb-0:3:1.0", "/sys/c");
+        let mut late_tty = device(4, "pci-0000:00:14.0-usb-0:4:1.0", "/sys/d");
+        let previous = vec![kept.clone(), removed, late_tty.clone()];
+        late_tty.system_path_alt = Some("/dev/ttyUSB0".to_string());
+        let current = vec![kept, added, late_tty];
+
+        let changes = diff_devices(&previous, &current);
+
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(&changes[0], DeviceChange::Added(d) if d.pid == 3));
+        assert!(matches!(
+            &changes[1],
+            DeviceChange::Changed { before, after }
+                if before.system_path_alt.is_none()
+                    && after.system_path_alt.as_deref() == Some("/dev/ttyUSB0")
+        ));
+        assert!(matches!(&changes[2], DeviceChange::Removed(d) if d.pid == 2));
+    }
+
+    #[test]
+    fn identical_snapshots_have_no_changes() {
+        let devices = vec![device(1, "N/A", "/sys/a"), device(1, "N/A", "/sys/b")];
+        assert!(diff_devices(&devices, &devices).is_empty());
+    }
+
+    #[test]
+    fn falls_back_to_system_path_without_port_path() {
+        // 两个相同 VID/PID 的设备，没有端口路径时靠 system_path 区分
+        let a = device(1, "N/A", "/sys/a");
+        let b = device(1, "N/A", "/sys/b");
+        assert_ne!(DeviceKey::of(&a), DeviceKey::of(&b));
+
+        let current = vec![a.clone(), b];
+        let changes = diff_devices(std::slice::from_ref(&a), &current);
+        assert!(
+            matches!(changes.as_slice(), [DeviceChange::Added(d)] if d.system_path == "/sys/b")
+        );
+    }
+
+    #[test]
+    fn applying_the_diff_reproduces_the_current_scan() {
+        let previous = vec![device(1, "port-1", "/sys/a"), device(2, "port-2", "/sys/b")];
+        let mut moved = device(1, "port-1", "/sys/a2");
+        moved.serial = Some("SN".to_string());
+        let current = vec![moved, device(3, "port-3", "/sys/c")];
+
+        let mut devices = previous.clone();
+        apply_changes(&mut devices, &diff_devices(&previous, &current));
+
+        let mut keys: Vec<DeviceKey> = devices.iter().map(DeviceKey::of).collect();
+        let mut expected: Vec<DeviceKey> = current.iter().map(DeviceKey::of).collect();
+        keys.sort();
+        expected.sort();
+        assert_eq!(keys, expected);
+        assert!(diff_devices(&devices, &current).is_empty());
+    }
+}
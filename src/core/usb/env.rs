@@ -0,0 +1,223 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{Result, bail};
+
+use crate::core::usb::models::ResolvedRole;
+
+/// 环境变量名前缀：角色 `top_camera` -> `DORA_ROLE_TOP_CAMERA`
+pub const ENV_PREFIX: &str = "DORA_ROLE_";
+
+/// `doratool env` / `GET /api/env` 的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EnvFormat {
+    // `export NAME='value'`，可直接 `eval "$(doratool env)"`
+    #[default]
+    Shell,
+    // `NAME="value"`，供 docker --env-file / dotenv 使用
+    Dotenv,
+    // `Environment="NAME=value"`，可放进 systemd unit 的 drop-in
+    Systemd,
+    // `{"NAME": "value"}`
+    Json,
+}
+
+impl fmt::Display for EnvFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Shell => "shell",
+            Self::Dotenv => "dotenv",
+            Self::Systemd => "systemd",
+            Self::Json => "json",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for EnvFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shell" => Ok(Self::Shell),
+            "dotenv" => Ok(Self::Dotenv),
+            "systemd" => Ok(Self::Systemd),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "unknown format '{}' (expected shell, dotenv, systemd or json)",
+                s
+            )),
+        }
+    }
+}
+
+/// 角色名 -> 合法的环境变量名
+/// 字母数字转大写，其它字符 (`-`、`.`、空格、非 ASCII 等) 都替换成 `_`
+pub fn env_var_name(role: &str) -> String {
+    let name: String = role
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}", ENV_PREFIX, name)
+}
+
+/// 已绑定角色 -> (变量名, 路径)，保持传入顺序
+/// 两个角色清洗后得到同一个变量名时报错 (例如 `left-arm` 和 `left_arm`)，避免一个悄悄覆盖另一个
+/// (`service::validate_rules` 会提前拒绝这样的配置，这里是兜底)
+pub fn env_vars(resolved: &[ResolvedRole]) -> Result<Vec<(String, String)>> {
+    let mut vars: Vec<(String, String)> = Vec::new();
+    for (index, r) in resolved.iter().enumerate() {
+        let name = env_var_name(&r.role);
+        if let Some(other) = resolved[..index]
+            .iter()
+            .find(|o| env_var_name(&o.role) == name)
+        {
+            bail!(
+                "roles '{}' and '{}' both map to environment variable {}",
+                other.role,
+                r.role,
+                name
+            );
+        }
+        vars.push((name, r.path.clone()));
+    }
+    Ok(vars)
+}
+
+/// 按格式输出 (每行一个变量，json 为一个对象)
+pub fn render(vars: &[(String, String)], format: EnvFormat) -> String {
+    match format {
+        // 单引号内不做任何转义，单引号本身写成 '\''
+        EnvFormat::Shell => lines(vars, |name, value| {
+            format!("export {}='{}'", name, value.replace('\'', r"'\''"))
+        }),
+        EnvFormat::Dotenv => lines(vars, |name, value| {
+            format!("{}=\"{}\"", name, escape_double_quoted(value))
+        }),
+        EnvFormat::Systemd => lines(vars, |name, value| {
+            format!("Environment=\"{}={}\"", name, escape_double_quoted(value))
+        }),
+        EnvFormat::Json => {
+            let object: serde_json::Map<String, serde_json::Value> = vars
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone().into()))
+                .collect();
+            serde_json::to_string_pretty(&object).unwrap_or_default() + "\n"
+        }
+    }
+}
+
+fn lines(vars: &[(String, String)], line: impl Fn(&str, &str) -> String) -> String {
+    vars.iter()
+        .map(|(name, value)| line(name, value) + "\n")
+        .collect()
+}
+
+fn escape_double_quoted(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::usb::models::RawDevice;
+
+    fn resolved(role: &str, path: &str) -> ResolvedRole {
+        ResolvedRole {
+            role: role.to_string(),
+            path: path.to_string(),
+            system_path: String::new(),
+            device: RawDevice {
+                vid: 0x1234,
+                pid: 0x5678,
+                serial: None,
+                port_path: "N/A".to_string(),
+                system_path: String::new(),
+                system_path_alt: None,
+            },
+        }
+    }
+
+    fn vars(value: &str) -> Vec<(String, String)> {
+        vec![("DORA_ROLE_CAM".to_string(), value.to_string())]
+    }
+
+    #[test]
+    fn env_var_name_sanitizes_role() {
+        assert_eq!(env_var_name("top_camera"), "DORA_ROLE_TOP_CAMERA");
+        assert_eq!(env_var_name("left-arm.2"), "DORA_ROLE_LEFT_ARM_2");
+        assert_eq!(env_var_name("相机 1"), "DORA_ROLE____1");
+    }
+
+    #[test]
+    fn env_vars_keeps_order() {
+        let vars =
+            env_vars(&[resolved("b", "/dev/ttyUSB1"), resolved("a", "/dev/ttyUSB0")]).unwrap();
+        assert_eq!(
+            vars,
+            vec![
+                ("DORA_ROLE_B".to_string(), "/dev/ttyUSB1".to_string()),
+                ("DORA_ROLE_A".to_string(), "/dev/ttyUSB0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn env_vars_rejects_colliding_names() {
+        let err = env_vars(&[
+            resolved("left-arm", "/dev/a"),
+            resolved("left_arm", "/dev/b"),
+        ])
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("'left-arm' and 'left_arm'"), "{}", err);
+        assert!(err.contains("DORA_ROLE_LEFT_ARM"), "{}", err);
+    }
+
+    #[test]
+    fn shell_quotes_single_quotes() {
+        assert_eq!(
+            render(&vars("/dev/it's $HOME"), EnvFormat::Shell),
+            "export DORA_ROLE_CAM='/dev/it'\\''s $HOME'\n"
+        );
+    }
+
+    #[test]
+    fn dotenv_and_systemd_escape_backslashes_and_double_quotes() {
+        let vars = vars(r#"C:\dev "x""#);
+        assert_eq!(
+            render(&vars, EnvFormat::Dotenv),
+            "DORA_ROLE_CAM=\"C:\\\\dev \\\"x\\\"\"\n"
+        );
+        assert_eq!(
+            render(&vars, EnvFormat::Systemd),
+            "Environment=\"DORA_ROLE_CAM=C:\\\\dev \\\"x\\\"\"\n"
+        );
+    }
+
+    #[test]
+    fn json_is_an_object() {
+        let value: serde_json::Value =
+            serde_json::from_str(&render(&vars("/dev/\"cam\""), EnvFormat::Json)).unwrap();
+        assert_eq!(value["DORA_ROLE_CAM"], "/dev/\"cam\"");
+        assert_eq!(render(&[], EnvFormat::Shell), "");
+    }
+
+    #[test]
+    fn format_round_trips() {
+        for format in [
+            EnvFormat::Shell,
+            EnvFormat::Dotenv,
+            EnvFormat::Systemd,
+            EnvFormat::Json,
+        ] {
+            assert_eq!(format.to_string().parse::<EnvFormat>(), Ok(format));
+        }
+        assert!("yaml".parse::<EnvFormat>().is_err());
+    }
+}
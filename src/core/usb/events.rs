@@ -0,0 +1,284 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+use usb_resolver::RawDeviceInfo;
+
+use crate::core::usb::{
+    diff::{DeviceChange, DeviceKey},
+    models::DeviceView,
+};
+
+/// Events published on the in-process event bus (`AppState::events`)
+// 进程内事件总线上发布的事件 (`AppState::events`)
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AppEvent {
+    // 设备插入
+    DeviceAttached { device: DeviceView },
+    // 设备拔出 (device 为拔出前的信息)
+    DeviceDetached { device: DeviceView },
+    // 同一设备的信息发生变化 (例如 tty 节点出现)
+    DeviceChanged { device: DeviceView },
+    // 角色绑定到了一个设备 (设备上线，或规则变更)
+    RoleBound { role: String, device: DeviceView },
+    // 角色失去了设备 (device 为之前绑定的设备)
+    RoleUnbound { role: String, device: DeviceView },
+    // 规则被替换 (Web 保存 / 文件重载)
+    RulesChanged { count: usize },
+    // USB 扫描失败
+    ScanError { message: String },
+}
+
+/// An event with its bus sequence number
+/// `id` increases monotonically for the lifetime of the process (used as SSE `id:`).
+// 带序号的事件
+// `id` 在进程生命周期内单调递增 (用作 SSE 的 `id:`)
+#[derive(Debug, Clone, Serialize)]
+pub struct EventEnvelope {
+    pub id: u64,
+    // Unix 时间戳 (毫秒)
+    pub timestamp_ms: u64,
+    #[serde(flatten)]
+    pub event: AppEvent,
+}
+
+/// Result of `EventBus::subscribe_since`
+// `EventBus::subscribe_since` 的返回值
+pub struct Replay {
+    // 需要补发的历史事件 (id 升序)
+    pub events: Vec<EventEnvelope>,
+    // false 表示请求的 id 已经被挤出历史缓冲区，中间有事件丢失，客户端应该全量刷新
+    pub complete: bool,
+    // 实时事件的接收端 (不会和 events 重复)
+    pub receiver: broadcast::Receiver<EventEnvelope>,
+}
+
+/// In-process event bus: a broadcast channel plus a bounded history for resuming
+// 进程内事件总线：broadcast 通道 + 有限长度的历史记录 (用于断线续传)
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<EventEnvelope>,
+    history: Arc<Mutex<History>>,
+    capacity: usize,
+}
+
+#[derive(Debug)]
+struct History {
+    next_id: u64,
+    events: VecDeque<EventEnvelope>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            sender,
+            history: Arc::new(Mutex::new(History {
+                next_id: 1,
+                events: VecDeque::with_capacity(capacity),
+            })),
+            capacity,
+        }
+    }
+
+    /// 发布事件，返回分配到的 id；没有订阅者时不算错误
+    pub fn publish(&self, event: AppEvent) -> u64 {
+        // 在历史锁内分配 id 并发送，保证订阅者看到的 id 严格递增
+        let mut history = self.history.lock().unwrap();
+
+        let envelope = EventEnvelope {
+            id: history.next_id,
+            timestamp_ms: now_ms(),
+            event,
+        };
+        history.next_id += 1;
+
+        if history.events.len() == self.capacity {
+            history.events.pop_front();
+        }
+        history.events.push_back(envelope.clone());
+
+        let _ = self.sender.send(envelope.clone());
+        envelope.id
+    }
+
+    /// 只订阅实时事件
+    pub fn subscribe(&self) -> broadcast::Receiver<EventEnvelope> {
+        self.sender.subscribe()
+    }
+
+    /// 订阅实时事件，并取出 id 大于 `last_id` 的历史事件
+    /// 两者在同一把锁内完成，补发的历史和实时事件之间既不重复也不遗漏
+    pub fn subscribe_since(&self, last_id: Option<u64>) -> Replay {
+        let history = self.history.lock().unwrap();
+        let receiver = self.sender.subscribe();
+
+        let Some(last_id) = last_id else {
+            return Replay {
+                events: Vec::new(),
+                complete: true,
+                receiver,
+            };
+        };
+
+        let events: Vec<EventEnvelope> = history
+            .events
+            .iter()
+            .filter(|e| e.id > last_id)
+            .cloned()
+            .collect();
+
+        // 最早保留的事件之前还有客户端没收到的事件，或者 id 比当前还大 (服务重启过，id 重新计数)
+        let oldest = history.events.front().map_or(history.next_id, |e| e.id);
+        let complete = last_id.saturating_add(1) >= oldest && last_id < history.next_id;
+
+        Replay {
+            events,
+            complete,
+            receiver,
+        }
+    }
+}
+
+/// 当前时间 (Unix 毫秒)
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 把扫描差异转换为设备事件
+/// 视图取自变更前后的完整匹配结果 (拔出的设备查 before，其余查 after)，
+/// 这样事件里的角色和冲突信息与 `/api/devices` 一致
+pub fn device_events(
+    changes: &[DeviceChange],
+    before: &[DeviceView],
+    after: &[DeviceView],
+) -> Vec<AppEvent> {
+    changes
+        .iter()
+        .map(|change| match change {
+            DeviceChange::Added(raw) => AppEvent::DeviceAttached {
+                device: find_view(after, raw),
+            },
+            DeviceChange::Removed(raw) => AppEvent::DeviceDetached {
+                device: find_view(before, raw),
+            },
+            DeviceChange::Changed { after: raw, .. } => AppEvent::DeviceChanged {
+                device: find_view(after, raw),
+            },
+        })
+        .collect()
+}
+
+/// 对比变更前后的角色绑定，生成 RoleUnbound / RoleBound 事件
+/// 同一个角色换了设备时，先发 Unbound 再发 Bound
+pub fn role_events(
+    before: &BTreeMap<String, DeviceView>,
+    after: &BTreeMap<String, DeviceView>,
+) -> Vec<AppEvent> {
+    let mut events = Vec::new();
+
+    for (role, old) in before {
+        if after.get(role).map(|new| &new.id) != Some(&old.id) {
+            events.push(AppEvent::RoleUnbound {
+                role: role.clone(),
+                device: old.clone(),
+            });
+        }
+    }
+
+    for (role, new) in after {
+        if before.get(role).map(|old| &old.id) != Some(&new.id) {
+            events.push(AppEvent::RoleBound {
+                role: role.clone(),
+                device: new.clone(),
+            });
+        }
+    }
+
+    events
+}
+
+fn find_view(views: &[DeviceView], raw: &RawDeviceInfo) -> DeviceView {
+    let id = DeviceKey::of(raw).to_string();
+    views
+        .iter()
+        .find(|view| view.id == id)
+        .cloned()
+        // 视图由同一次扫描生成，正常不会走到这里 (拿不到完整设备列表，拓扑的 root hub 序号按 0 处理)
+        .unwrap_or_else(|| DeviceView::new(raw.clone(), &[]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bus_with_events(capacity: usize, count: usize) -> EventBus {
+        let bus = EventBus::new(capacity);
+        for i in 0..count {
+            bus.publish(AppEvent::RulesChanged { count: i });
+        }
+        bus
+    }
+
+    fn ids(replay: &Replay) -> Vec<u64> {
+        replay.events.iter().map(|e| e.id).collect()
+    }
+
+    #[test]
+    fn replays_events_after_last_id() {
+        let bus = bus_with_events(8, 5);
+
+        let replay = bus.subscribe_since(Some(3));
+        assert_eq!(ids(&replay), vec![4, 5]);
+        assert!(replay.complete);
+
+        let replay = bus.subscribe_since(Some(5));
+        assert!(replay.events.is_empty());
+        assert!(replay.complete);
+
+        let replay = bus.subscribe_since(None);
+        assert!(replay.events.is_empty());
+        assert!(replay.complete);
+    }
+
+    #[test]
+    fn reports_gap_when_history_was_evicted() {
+        // 容量 3，发布 5 条后只剩 3、4、5
+        let bus = bus_with_events(3, 5);
+
+        let replay = bus.subscribe_since(Some(2));
+        assert_eq!(ids(&replay), vec![3, 4, 5]);
+        assert!(replay.complete);
+
+        let replay = bus.subscribe_since(Some(1));
+        assert_eq!(ids(&replay), vec![3, 4, 5]);
+        assert!(!replay.complete);
+    }
+
+    #[test]
+    fn id_from_the_future_is_incomplete() {
+        // 服务重启后 id 重新计数，客户端带着旧的 (更大的) id 回来
+        let bus = bus_with_events(8, 2);
+
+        let replay = bus.subscribe_since(Some(10));
+        assert!(replay.events.is_empty());
+        assert!(!replay.complete);
+    }
+
+    #[test]
+    fn max_last_event_id_does_not_overflow() {
+        let bus = bus_with_events(8, 2);
+
+        let replay = bus.subscribe_since(Some(u64::MAX));
+        assert!(replay.events.is_empty());
+        assert!(!replay.complete);
+    }
+}
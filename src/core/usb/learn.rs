@@ -0,0 +1,272 @@
+use std::{fs, path::Path, time::Duration};
+
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+use usb_resolver::RawDeviceInfo;
+
+use crate::{
+    core::usb::{
+        diff::{self, DeviceChange, DeviceKey},
+        events::AppEvent,
+        models::{BindStrategy, DeviceConfig, DeviceView},
+        rules::{self, RuleError},
+        service,
+        topology::Topology,
+    },
+    infra::state::AppState,
+};
+
+/// 发现新设备后再等一会儿，让 Hub 下面的设备完成枚举，然后重新挑选
+/// (插入带 Hub 的设备时，Hub 总是先于它下面的设备出现)
+pub const SETTLE_WINDOW: Duration = Duration::from_millis(1500);
+
+/// 学习模式的结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum LearnOutcome {
+    // 插入了一个新设备，已经为它生成并保存规则
+    Bound {
+        rule: DeviceConfig,
+        device: DeviceView,
+    },
+    // 拔出了一个已绑定的设备，只用于辨认它是哪个角色 (不修改规则)
+    Identified {
+        role: String,
+        device: DeviceView,
+    },
+}
+
+/// 学习开始时的设备快照与当前设备列表对比后挑出的设备
+#[derive(Debug, Clone)]
+pub enum Learned {
+    // 新插入的设备 (已跳过 Hub)
+    Attached(RawDeviceInfo),
+    // 拔出了一个已绑定的设备
+    Detached { role: String, device: RawDeviceInfo },
+}
+
+/// 对比学习开始时的快照 `baseline` 和当前设备列表，挑出要学习的设备
+/// 服务端 (事件驱动) 和命令行 (本地轮询) 共用：始终与同一个快照比较，错过中间的事件也不会漏掉插入。
+/// 新插入的设备优先：跳过 Hub，同一批插入多个设备时选最末端的那个；
+/// 没有新设备时，看是否拔出了一个已绑定的设备
+pub fn pick_device(
+    baseline: &[RawDeviceInfo],
+    current: &[RawDeviceInfo],
+    rules: &[DeviceConfig],
+) -> Option<Learned> {
+    let changes = diff::diff_devices(baseline, current);
+
+    let added: Vec<&RawDeviceInfo> = changes
+        .iter()
+        .filter_map(|change| match change {
+            DeviceChange::Added(raw) if !is_hub(raw) => Some(raw),
+            _ => None,
+        })
+        .collect();
+    if let Some(leaf) = leaf_most(&added, current) {
+        return Some(Learned::Attached(leaf.clone()));
+    }
+
+    changes.into_iter().find_map(|change| match change {
+        DeviceChange::Removed(raw) => {
+            service::match_rule(&raw, rules, baseline).map(|rule| Learned::Detached {
+                role: rule.role.clone(),
+                device: raw,
+            })
+        }
+        _ => None,
+    })
+}
+
+/// 学习模式：等待下一个新插入的设备，并把它绑定到 `role`
+/// 规则由 `rules::bind_device` 生成 (不指定策略时使用默认策略)
+/// 期间拔出一个已绑定的设备会结束等待，并返回它的角色
+/// 超时返回 `Ok(None)`
+pub async fn learn(
+    state: &AppState,
+    role: &str,
+    strategy: Option<BindStrategy>,
+    timeout: Duration,
+) -> Result<Option<LearnOutcome>, RuleError> {
+    // 先检查角色，避免等了半天才发现角色已存在
+    if state.rules.read().unwrap().iter().any(|r| r.role == role) {
+        return Err(RuleError::Conflict(role.to_string()));
+    }
+
+    // 先订阅再拍快照，快照之后的变化一定会唤醒下面的循环
+    let mut events = state.subscribe();
+    let baseline = state.live_devices.read().unwrap().clone();
+
+    let wait = async {
+        loop {
+            match events.recv().await {
+                Ok(envelope) if !is_device_event(&envelope.event) => continue,
+                // 错过事件时同样重新对比快照，插入不会因此丢失
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return Ok(None),
+            }
+
+            let rules = state.rules.read().unwrap().clone();
+            let current = state.live_devices.read().unwrap().clone();
+            let raw = match pick_device(&baseline, &current, &rules) {
+                None => continue,
+                Some(Learned::Detached { role, device }) => {
+                    let mut device = DeviceView::new(device, &baseline);
+                    device.role = Some(role.clone());
+                    return Ok(Some(LearnOutcome::Identified { role, device }));
+                }
+                Some(Learned::Attached(_)) => {
+                    tokio::time::sleep(SETTLE_WINDOW).await;
+                    let current = state.live_devices.read().unwrap().clone();
+                    match pick_device(&baseline, &current, &rules) {
+                        Some(Learned::Attached(raw)) => raw,
+                        // 等待期间又被拔掉了
+                        _ => continue,
+                    }
+                }
+            };
+
+            let current = state.live_devices.read().unwrap().clone();
+            if let Some(existing) = service::match_rule(&raw, &rules, &current) {
+                return Err(RuleError::Invalid(format!(
+                    "device {} is already bound to role '{}'",
+                    DeviceKey::of(&raw),
+                    existing.role
+                )));
+            }
+            let view = rules::bind_device(state, &DeviceKey::of(&raw).to_string(), role, strategy)?;
+            let rule = rules::get_rule(state, role)?;
+            return Ok(Some(LearnOutcome::Bound { rule, device: view }));
+        }
+    };
+
+    tokio::time::timeout(timeout, wait)
+        .await
+        .unwrap_or(Ok(None))
+}
+
+fn is_device_event(event: &AppEvent) -> bool {
+    matches!(
+        event,
+        AppEvent::DeviceAttached { .. }
+            | AppEvent::DeviceDetached { .. }
+            | AppEvent::DeviceChanged { .. }
+    )
+}
+
+// 设备类 0x09 为 Hub (仅 Linux，从 sysfs 读取；读不到时视为普通设备)
+fn is_hub(raw: &RawDeviceInfo) -> bool {
+    fs::read_to_string(Path::new(&raw.system_path).join("bDeviceClass"))
+        .is_ok_and(|class| class.trim() == "09")
+}
+
+// 选出下面没有其它新设备的那个 (拓扑未知时视为末端)，多个时取扫描顺序的第一个
+fn leaf_most<'a>(
+    added: &[&'a RawDeviceInfo],
+    current: &[RawDeviceInfo],
+) -> Option<&'a RawDeviceInfo> {
+    let topologies: Vec<Option<Topology>> = added
+        .iter()
+        .map(|raw| Topology::parse(&raw.system_path, current))
+        .collect();
+
+    added
+        .iter()
+        .enumerate()
+        .find(|(i, _)| {
+            let Some(parent) = &topologies[*i] else {
+                return true;
+            };
+            !topologies
+                .iter()
+                .flatten()
+                .any(|child| child.relative_to(parent).is_some())
+        })
+        .map(|(_, raw)| *raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: &str = "/sys/devices/pci0000:00/0000:00:14.0/usb1";
+
+    fn device(pid: u16, system_path: &str) -> RawDeviceInfo {
+        RawDeviceInfo {
+            vid: 0x1234,
+            pid,
+            serial: None,
+            port_path: "N/A".to_string(),
+            system_path: format!("{}/{}", ROOT, system_path),
+            system_path_alt: None,
+        }
+    }
+
+    fn rule(role: &str, pid: u16) -> DeviceConfig {
+        DeviceConfig {
+            role: role.to_string(),
+            vid: 0x1234,
+            pid,
+            strategy: BindStrategy::VidPid,
+            serial: None,
+            port_path: None,
+            topology: None,
+            priority: 0,
+            required: false,
+        }
+    }
+
+    #[test]
+    fn picks_device_behind_a_newly_attached_hub() {
+        let keyboard = device(1, "1-1");
+        let baseline = vec![keyboard.clone()];
+        // Hub 先出现在扫描结果里，它下面的摄像头在后面
+        let current = vec![keyboard, device(2, "1-2"), device(3, "1-2/1-2.4")];
+
+        match pick_device(&baseline, &current, &[]) {
+            Some(Learned::Attached(raw)) => assert_eq!(raw.pid, 3),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn picks_single_attached_device() {
+        let current = vec![device(2, "1-3")];
+        assert!(matches!(
+            pick_device(&[], &current, &[]),
+            Some(Learned::Attached(raw)) if raw.pid == 2
+        ));
+    }
+
+    #[test]
+    fn identifies_unplugged_bound_device() {
+        let baseline = vec![device(1, "1-1"), device(2, "1-2")];
+        let current = vec![device(2, "1-2")];
+        let rules = vec![rule("arm", 1)];
+
+        assert!(matches!(
+            pick_device(&baseline, &current, &rules),
+            Some(Learned::Detached { role, device }) if role == "arm" && device.pid == 1
+        ));
+    }
+
+    #[test]
+    fn ignores_unplugged_unbound_device_and_no_changes() {
+        let baseline = vec![device(1, "1-1"), device(2, "1-2")];
+        let current = vec![device(2, "1-2")];
+
+        assert!(pick_device(&baseline, &current, &[rule("arm", 9)]).is_none());
+        assert!(pick_device(&baseline, &baseline, &[]).is_none());
+    }
+
+    #[test]
+    fn attach_wins_over_detach_in_the_same_diff() {
+        let baseline = vec![device(1, "1-1")];
+        let current = vec![device(2, "1-2")];
+
+        assert!(matches!(
+            pick_device(&baseline, &current, &[rule("arm", 1)]),
+            Some(Learned::Attached(raw)) if raw.pid == 2
+        ));
+    }
+}
@@ -56,22 +56,27 @@
 //     });
 // }
 
-use crossbeam_channel::{select, unbounded};
-use std::collections::HashSet;
-use std::sync::{Arc, RwLock};
+use crossbeam_channel::{RecvTimeoutError, never, select, unbounded};
 use std::thread;
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
-use crate::core::usb::models::DeviceConfig;
-use crate::infra::state::AppState;
-use usb_resolver::{DeviceEvent, get_monitor}; // 确保引入
+use crate::core::usb::{
+    diff::{self, DeviceChange, DeviceKey},
+    events::AppEvent,
+    models::DeviceConfig,
+    service::{self, RuleMatch},
+};
+use crate::infra::{settings::Settings, state::AppState};
+use usb_resolver::{DeviceEvent, RawDeviceInfo, get_monitor}; // 确保引入
 
-pub fn start_background_monitor(state: AppState) {
+pub fn start_background_monitor(state: AppState, settings: &Settings) {
     // 1. 启动 Polling 线程 (负责发现新设备，但可能会阻塞)
     let state_for_poll = state.clone();
+    let scan_interval = settings.scan_interval();
+    let slow_scan_threshold = settings.slow_scan_threshold();
     thread::spawn(move || {
-        run_polling_loop(state_for_poll);
+        run_polling_loop(state_for_poll, scan_interval, slow_scan_threshold);
     });
 
     // 2. 启动 Event 线程 (负责已配置设备的极速热插拔)
@@ -81,6 +86,12 @@ pub fn start_background_monitor(state: AppState) {
     });
 }
 
+/// 同步执行一次全量扫描 (不启动后台线程)
+/// 供 CLI 等一次性命令使用
+pub fn scan_once() -> anyhow::Result<Vec<RawDeviceInfo>> {
+    get_monitor().scan_now()
+}
+
 /// 任务 A: 事件监听 (解决拔出卡顿的核心)
 fn run_event_listener(state: AppState) {
     info!("🚀 [Thread-Event] USB 热插拔监听已启动 (即时响应)");
@@ -89,68 +100,113 @@ fn run_event_listener(state: AppState) {
     let monitor = get_monitor();
 
     // 获取当前的规则快照
-    // 注意：如果运行期间修改了规则，这里最好有个机制能更新 monitor，
-    // 但为了简单，目前仅使用启动时的规则。
+    // 规则在运行期间被修改时 (AppState::set_rules)，会通过 rules_changed 通知这里刷新，
+    // 这样新绑定的角色也能立即获得极速热插拔处理，无需重启。
+    let mut rules = state.rules.read().unwrap().clone();
 
     if let Err(e) = monitor.start(tx) {
         error!("无法启动内核事件监听: {}", e);
         return;
     }
 
+    // 规则通知通道断开后换成 never()，否则断开的通道会一直就绪，select! 变成空转
+    let mut rules_changed = state.rules_changed_rx.clone();
+
     loop {
-        match rx.recv() {
-            Ok(event) => {
-                match event {
-                    DeviceEvent::Attached(resolved) => {
-                        info!("⚡ [Event] 设备极速上线: {}", resolved.system_path);
-                        // Attached 通常也会被 Polling 扫到，这里可以不做操作，
-                        // 或者为了快，手动触发一次 scan (但 scan 可能会阻塞)
-                    }
-                    DeviceEvent::Detached(role_name) => {
-                        info!("⚡ [Event] 设备极速下线: {}", role_name);
-
-                        // --- 关键操作：绕过阻塞的 Scan，直接操作内存 ---
-                        let mut devices = state.live_devices.write().unwrap();
-                        let current_rules = state.rules.read().unwrap();
-
-                        // 我们需要找到这个 role 对应的 VID/PID，然后在 raw_devices 里把它删掉
-                        if let Some(rule) = current_rules.iter().find(|r| r.role == role_name) {
-                            let before_len = devices.len();
-
-                            // 从列表中移除匹配该规则的设备
-                            devices.retain(|d| {
-                                // 如果 VID/PID 匹配，且序列号(如果有)也匹配，就删掉它
-                                let vid_match = d.vid == rule.vid;
-                                let pid_match = d.pid == rule.pid;
-                                let serial_match = rule.serial == d.serial;
-
-                                // 如果所有条件都符合，说明这就是那个被拔掉的设备，返回 false (删除)
-                                !(vid_match && pid_match && serial_match)
-                            });
-
-                            let after_len = devices.len();
-                            if before_len != after_len {
-                                info!("✨ 已从内存中强制移除设备: {} (无需等待 Scan)", role_name);
-                            }
-                        }
-                    }
+        select! {
+            recv(rx) -> msg => match msg {
+                Ok(event) => handle_device_event(&state, &rules, event),
+                Err(e) => {
+                    error!("Event Channel Closed: {}", e);
+                    break;
                 }
+            },
+            recv(rules_changed) -> msg => {
+                if msg.is_err() {
+                    warn!("⚠️ [Thread-Event] 规则通知通道已关闭，不再自动刷新规则");
+                    rules_changed = never();
+                    continue;
+                }
+                // 连续多次保存只需要刷新一次
+                while rules_changed.try_recv().is_ok() {}
+
+                rules = state.rules.read().unwrap().clone();
+                info!("🔄 [Thread-Event] 规则已更新，当前监听 {} 个角色", rules.len());
             }
-            Err(e) => {
-                error!("Event Channel Closed: {}", e);
-                break;
-            }
+        }
+    }
+}
+
+/// 处理一条内核事件
+/// 只有命中规则 (已配置角色) 的设备才会绕过 Scan 直接修改内存
+fn handle_device_event(state: &AppState, rules: &[DeviceConfig], event: DeviceEvent) {
+    match event {
+        DeviceEvent::Attached(raw) => {
+            // Attached 通常也会被 Polling 扫到，这里只对已配置的角色提前上线
+            // (scan 可能会阻塞，不在这里触发)
+            // Hub 锚点需要在线设备列表 (上游 Hub 总是先于下游设备出现)
+            let devices = state.live_devices.read().unwrap().clone();
+            let rule = match service::resolve_rule(&raw, rules, &devices) {
+                RuleMatch::Unique(rule) => rule,
+                RuleMatch::Tie(tied) => {
+                    let roles: Vec<&str> = tied.iter().map(|r| r.role.as_str()).collect();
+                    warn!(
+                        "⚠️ [Event] 设备 {} 同时命中优先级相同的规则 {:?}，不绑定任何角色",
+                        DeviceKey::of(&raw),
+                        roles
+                    );
+                    return;
+                }
+                RuleMatch::None => return,
+            };
+            info!(
+                "⚡ [Event] 设备极速上线: {} -> {}",
+                rule.role, raw.system_path
+            );
+
+            state.update_devices(|devices| {
+                let key = DeviceKey::of(&raw);
+                let change = match devices.iter().find(|d| DeviceKey::of(d) == key) {
+                    Some(existing) => DeviceChange::Changed {
+                        before: existing.clone(),
+                        after: raw,
+                    },
+                    None => DeviceChange::Added(raw),
+                };
+                let changes = vec![change];
+                diff::apply_changes(devices, &changes);
+                changes
+            });
+        }
+        DeviceEvent::Detached(system_path) => {
+            // 内核给出的是设备的 system_path (syspath)，不是角色名
+            // --- 关键操作：绕过阻塞的 Scan，直接操作内存 ---
+            state.update_devices(|devices| {
+                let Some(index) = devices.iter().position(|d| d.system_path == system_path) else {
+                    return Vec::new();
+                };
+                let Some(rule) = service::match_rule(&devices[index], rules, devices) else {
+                    return Vec::new();
+                };
+
+                info!("⚡ [Event] 设备极速下线: {}", rule.role);
+                let removed = devices.remove(index);
+                info!("✨ 已从内存中强制移除设备: {} (无需等待 Scan)", rule.role);
+
+                vec![DeviceChange::Removed(removed)]
+            });
         }
     }
 }
 
 /// 任务 B: 轮询扫描 (负责兜底和发现未知设备)
-fn run_polling_loop(state: AppState) {
+fn run_polling_loop(state: AppState, scan_interval: Duration, slow_scan_threshold: Duration) {
     info!("🐢 [Thread-Poll] USB 轮询扫描已启动 (发现新设备)");
 
     // 这里的 monitor 专门用于 scan
     let monitor = get_monitor();
-    let mut last_seen_fingerprints = HashSet::new();
+    // 相同的扫描错误只发布一次，避免每 300ms 刷屏
+    let mut last_scan_error: Option<String> = None;
 
     loop {
         let start = Instant::now();
@@ -158,29 +214,35 @@ fn run_polling_loop(state: AppState) {
         // 这一步在设备拔出时会阻塞 5~10 秒
         match monitor.scan_now() {
             Ok(raw_devices) => {
-                // 生成指纹用于日志 (同之前逻辑)
-                let mut current_fingerprints = HashSet::new();
-                for dev in &raw_devices {
-                    let key = format!("{:04x}:{:04x}:{:?}", dev.vid, dev.pid, dev.port_path);
-                    if !last_seen_fingerprints.contains(&key) {
-                        info!("🔍 [Poll] 扫描到设备: (VID:{:04x})", dev.vid);
+                last_scan_error = None;
+
+                // 与当前内存中的设备列表做增量对比，只更新有变化的条目
+                // 注意：对比的基准是 live_devices 而不是上一次扫描结果，
+                // 所以 Event 线程已经提前移除/上线的设备不会被重复处理。
+                state.update_devices(|devices| {
+                    let changes = diff::diff_devices(devices, &raw_devices);
+                    diff::apply_changes(devices, &changes);
+                    for change in &changes {
+                        log_change(change);
                     }
-                    current_fingerprints.insert(key);
+                    changes
+                });
+            }
+            Err(e) => {
+                error!("Scan failed: {}", e);
+
+                let message = e.to_string();
+                if last_scan_error.as_ref() != Some(&message) {
+                    state.publish(AppEvent::ScanError {
+                        message: message.clone(),
+                    });
+                    last_scan_error = Some(message);
                 }
-                last_seen_fingerprints = current_fingerprints;
-
-                // 更新内存
-                // 注意：这里会直接覆盖 Event 线程的修改，
-                // 但因为 Scan 发生了阻塞，当它运行到这里时，raw_devices 里肯定已经没有那个设备了。
-                // 所以最终状态是一致的。
-                let mut w = state.live_devices.write().unwrap();
-                *w = raw_devices;
             }
-            Err(e) => error!("Scan failed: {}", e),
         }
 
         let duration = start.elapsed();
-        if duration > Duration::from_secs(1) {
+        if duration > slow_scan_threshold {
             warn!(
                 "⚠️  USB 扫描发生了 I/O 阻塞: {:.2}s (这是正常的 OS 行为，但 Event 线程已提前更新 UI)",
                 duration.as_secs_f32()
@@ -188,8 +250,41 @@ fn run_polling_loop(state: AppState) {
             // 如果刚刚卡了很久，说明刚发生了拔出，立即进行下一次扫描可能意义不大
             // 且不需要 sleep 太多，因为已经睡了 7 秒了
         } else {
-            // 正常情况睡 300ms
-            thread::sleep(Duration::from_millis(300));
+            // 正常情况按配置的间隔休眠 (默认 300ms)，收到 rescan 请求时提前醒来
+            match state.rescan_rx.recv_timeout(scan_interval) {
+                Ok(()) => {
+                    while state.rescan_rx.try_recv().is_ok() {}
+                    debug!("🔁 [Poll] 收到立即扫描请求");
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                // 通道断开时 recv_timeout 会立即返回，仍然要休眠，避免空转
+                Err(RecvTimeoutError::Disconnected) => thread::sleep(scan_interval),
+            }
+        }
+    }
+}
+
+fn log_change(change: &DeviceChange) {
+    match change {
+        DeviceChange::Added(raw) => {
+            info!(
+                "🔍 [Poll] 扫描到设备: {} ({})",
+                change.key(),
+                raw.system_path
+            )
+        }
+        DeviceChange::Removed(raw) => {
+            info!(
+                "👋 [Poll] 设备已移除: {} ({})",
+                change.key(),
+                raw.system_path
+            )
         }
+        DeviceChange::Changed { before, after } => debug!(
+            "♻️  [Poll] 设备信息变化: {} ({} -> {})",
+            change.key(),
+            before.system_path,
+            after.system_path
+        ),
     }
 }
@@ -1,3 +1,12 @@
+pub mod dataflow;
+pub mod diff;
+pub mod env;
+pub mod events;
+pub mod learn;
 pub mod manager;
 pub mod models;
+pub mod pattern;
+pub mod rules;
 pub mod service;
+pub mod topology;
+pub mod wait;
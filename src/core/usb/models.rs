@@ -1,40 +1,587 @@
+use std::{fmt, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 use usb_resolver::RawDeviceInfo;
 
+use crate::core::usb::{diff::DeviceKey, pattern, topology::Topology};
+
 /// Configuration/Rules
 /// Keep as numbers for easy comparison and JSON storage conventions
 // 配置文件/规则 用户保存设备配置信息和读取
 // 保持数字，方便比对，且符合 JSON 存储习惯
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "DeviceConfigRepr")]
 pub struct DeviceConfig {
     pub role: String, // Require and Unique
     pub vid: u16,
     pub pid: u16,
-    pub serial: Option<String>,
-    pub port_path: String,
+    pub strategy: BindStrategy,
+    pub serial: Option<String>,    // Serial / PortSerial 时必填
+    pub port_path: Option<String>, // Port / PortSerial 时必填，支持通配符 (见 pattern 模块)
+    // Topology 时必填：锚点 + 相对端口链 (不依赖总线号)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topology: Option<TopologyAnchor>,
+    // 显式优先级，越大越优先；同一设备命中多条规则时先比优先级，再比具体程度
+    #[serde(default, skip_serializing_if = "is_default_priority")]
+    pub priority: i32,
+    // 必需的硬件：`doratool check` 在它离线时返回非零退出码
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+}
+
+/// Topology anchor: a named root plus the port chain below it
+// 拓扑锚点：一个有名字的根节点 + 它下面的相对端口链
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopologyAnchor {
+    pub root: AnchorRoot,
+    // 相对端口链，例如 "2.1"，支持通配符 (见 pattern 模块)
+    pub chain: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnchorRoot {
+    // 主控制器，例如 PCI 地址 "0000:00:14.0" (见 `Topology::controller`)
+    // root_hub 为控制器下 root hub 的序号 (见 `Topology::root_hub`)，旧规则没有该字段时匹配任意 root hub
+    Controller {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        root_hub: Option<usize>,
+    },
+    // 上游 Hub 自身的 VID/PID (可选序列号)
+    Hub {
+        vid: u16,
+        pid: u16,
+        #[serde(default)]
+        serial: Option<String>,
+    },
+}
+
+impl fmt::Display for TopologyAnchor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.root {
+            AnchorRoot::Controller { path, root_hub } => {
+                write!(f, "controller={}", path)?;
+                if let Some(root_hub) = root_hub {
+                    write!(f, "#{}", root_hub)?;
+                }
+            }
+            AnchorRoot::Hub { vid, pid, serial } => {
+                write!(f, "hub={:04x}:{:04x}", vid, pid)?;
+                if let Some(serial) = serial {
+                    write!(f, ":{}", serial)?;
+                }
+            }
+        }
+        write!(f, "/{}", self.chain)
+    }
+}
+
+impl FromStr for AnchorRoot {
+    type Err = String;
+
+    /// `controller=<path>[#<root_hub>]` 或 `hub=<vid>:<pid>[:<serial>]` (VID/PID 为十六进制)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid anchor '{}' (expected controller=<path>[#<root_hub>] or hub=<vid>:<pid>[:<serial>])",
+                s
+            )
+        };
+        match s.split_once('=') {
+            Some(("controller", value)) => {
+                let (path, root_hub) = match value.split_once('#') {
+                    Some((path, n)) => (path, Some(n.parse().map_err(|_| invalid())?)),
+                    None => (value, None),
+                };
+                if path.is_empty() {
+                    return Err(invalid());
+                }
+                Ok(Self::Controller {
+                    path: path.to_string(),
+                    root_hub,
+                })
+            }
+            Some(("hub", ids)) => {
+                let mut parts = ids.splitn(3, ':');
+                let mut hex = || {
+                    parts
+                        .next()
+                        .and_then(|p| u16::from_str_radix(p.trim_start_matches("0x"), 16).ok())
+                };
+                let (Some(vid), Some(pid)) = (hex(), hex()) else {
+                    return Err(invalid());
+                };
+                let serial = parts.next().filter(|s| !s.is_empty()).map(str::to_string);
+                Ok(Self::Hub { vid, pid, serial })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl DeviceConfig {
+    /// 规则的先后顺序：(优先级, 具体程度, 端口是否精确)，越大越优先
+    /// 两条规则命中同一设备且该值相同时视为冲突，不会按文件顺序随便选一条
+    pub fn precedence(&self) -> (i32, u8, bool) {
+        (
+            self.priority,
+            self.strategy.specificity(),
+            self.has_exact_port(),
+        )
+    }
+
+    /// port_path 是精确路径而不是通配符 (精确路径优先于通配符)
+    pub fn has_exact_port(&self) -> bool {
+        self.strategy.requires_port()
+            && self
+                .port_path
+                .as_deref()
+                .is_some_and(|p| !pattern::is_pattern(p))
+    }
+}
+
+fn is_default_priority(priority: &i32) -> bool {
+    *priority == 0
+}
+
+/// Binding strategy: which fields (besides VID/PID) must match
+// 绑定策略：除了 VID/PID 之外，还需要匹配哪些字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BindStrategy {
+    // 物理端口 (同型号设备插在固定的口上)
+    Port,
+    // 序列号 (设备可以随便换口)
+    Serial,
+    // 端口 + 序列号都要匹配
+    PortSerial,
+    // 只匹配 VID/PID (同型号只有一个设备时)
+    VidPid,
+    // 拓扑锚点 + 相对端口链 (总线号变化时端口规则依然有效)
+    Topology,
+}
+
+impl BindStrategy {
+    pub fn requires_port(self) -> bool {
+        matches!(self, Self::Port | Self::PortSerial)
+    }
+
+    pub fn requires_serial(self) -> bool {
+        matches!(self, Self::Serial | Self::PortSerial)
+    }
+
+    /// 具体程度：除 VID/PID 外还要求匹配的字段数
+    pub fn specificity(self) -> u8 {
+        u8::from(self.requires_port() || self.requires_topology())
+            + u8::from(self.requires_serial())
+    }
+
+    pub fn requires_topology(self) -> bool {
+        matches!(self, Self::Topology)
+    }
+
+    /// 旧版配置文件没有 strategy 字段，根据已填写的字段推断
+    /// (旧版匹配逻辑：port_path 必须一致，配了 serial 时 serial 也必须一致)
+    pub fn infer(serial: Option<&str>, port_path: Option<&str>) -> Self {
+        match (serial.is_some(), port_path.is_some()) {
+            (true, true) => Self::PortSerial,
+            (false, true) => Self::Port,
+            (true, false) => Self::Serial,
+            (false, false) => Self::VidPid,
+        }
+    }
+}
+
+impl fmt::Display for BindStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Port => "port",
+            Self::Serial => "serial",
+            Self::PortSerial => "port_serial",
+            Self::VidPid => "vid_pid",
+            Self::Topology => "topology",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for BindStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "port" => Ok(Self::Port),
+            "serial" => Ok(Self::Serial),
+            "port_serial" => Ok(Self::PortSerial),
+            "vid_pid" => Ok(Self::VidPid),
+            "topology" => Ok(Self::Topology),
+            _ => Err(format!(
+                "unknown strategy '{}' (expected port, serial, port_serial, vid_pid or topology)",
+                s
+            )),
+        }
+    }
+}
+
+/// On-disk / wire form of DeviceConfig: `strategy` is optional so that old rule files keep loading
+// DeviceConfig 的反序列化形式：strategy 可选，保证旧配置文件可以继续加载
+#[derive(Deserialize)]
+struct DeviceConfigRepr {
+    role: String,
+    vid: u16,
+    pid: u16,
+    #[serde(default)]
+    strategy: Option<BindStrategy>,
+    #[serde(default)]
+    serial: Option<String>,
+    #[serde(default)]
+    port_path: Option<String>,
+    #[serde(default)]
+    topology: Option<TopologyAnchor>,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    required: bool,
+}
+
+impl From<DeviceConfigRepr> for DeviceConfig {
+    fn from(repr: DeviceConfigRepr) -> Self {
+        // 空字符串视为未填写 (前端输入框清空时会发送 "")
+        let serial = repr.serial.filter(|s| !s.is_empty());
+        let port_path = repr.port_path.filter(|s| !s.is_empty());
+        let strategy = repr.strategy.unwrap_or_else(|| match repr.topology {
+            Some(_) => BindStrategy::Topology,
+            None => BindStrategy::infer(serial.as_deref(), port_path.as_deref()),
+        });
+
+        Self {
+            role: repr.role,
+            vid: repr.vid,
+            pid: repr.pid,
+            strategy,
+            serial,
+            port_path,
+            topology: repr.topology,
+            priority: repr.priority,
+            required: repr.required,
+        }
+    }
 }
 
 /// Front View
 // 前端视图 ( 直接以十六进制显示 "0x3290" )
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DeviceView {
+    pub id: String, // 稳定标识 (DeviceKey)，例如 "2e8a:000a@pci-0000:00:14.0-usb-0:2"
     pub role: Option<String>,
     pub vid: String, // 变更：直接发给前端 "0x3290"
     pub pid: String, // 变更：直接发给前端 "0x2645"
     pub serial: Option<String>,
     pub port_path: String,
     pub system_path: String,
+    // 与总线号无关的拓扑位置 (仅 Linux)，可直接用来写 Topology 规则
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topology: Option<Topology>,
+    // 与该设备相关的冲突；有冲突时 role 为空，不会悄悄选一个角色
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<Conflict>,
 }
 
-impl From<RawDeviceInfo> for DeviceView {
-    fn from(value: RawDeviceInfo) -> Self {
+/// Rule/device ambiguity detected while matching
+// 匹配时发现的歧义
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Conflict {
+    // 同一个角色命中了多个在线设备 (例如共用序列号的 CH340)
+    AmbiguousRole { role: String, devices: Vec<String> },
+    // 同一个设备命中了多条优先级和具体程度都相同的规则
+    RuleTie { device: String, roles: Vec<String> },
+}
+
+impl Conflict {
+    /// 冲突是否涉及某个设备 (DeviceView::id)
+    pub fn involves(&self, device_id: &str) -> bool {
+        match self {
+            Self::AmbiguousRole { devices, .. } => devices.iter().any(|d| d == device_id),
+            Self::RuleTie { device, .. } => device == device_id,
+        }
+    }
+
+    /// 冲突是否涉及某个角色
+    pub fn involves_role(&self, role: &str) -> bool {
+        match self {
+            Self::AmbiguousRole { role: r, .. } => r == role,
+            Self::RuleTie { roles, .. } => roles.iter().any(|r| r == role),
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AmbiguousRole { role, devices } => write!(
+                f,
+                "role '{}' matches {} devices: {}",
+                role,
+                devices.len(),
+                devices.join(", ")
+            ),
+            Self::RuleTie { device, roles } => write!(
+                f,
+                "device {} matches rules with equal precedence: {}",
+                device,
+                roles.join(", ")
+            ),
+        }
+    }
+}
+
+impl DeviceView {
+    /// 未匹配角色的设备视图
+    /// `devices` 为同一次扫描的完整设备列表，用于计算拓扑 (见 `Topology::parse`)
+    pub fn new(value: RawDeviceInfo, devices: &[RawDeviceInfo]) -> Self {
         Self {
+            id: DeviceKey::of(&value).to_string(),
             role: None,
             vid: format!("0x{:04x}", value.vid),
             pid: format!("0x{:04x}", value.pid),
             serial: value.serial,
+            topology: Topology::parse(&value.system_path, devices),
+            port_path: value.port_path,
+            system_path: value.system_path,
+            conflicts: Vec::new(),
+        }
+    }
+}
+
+/// A single matching criterion of a rule
+// 规则的单个匹配条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Criterion {
+    Vid,
+    Pid,
+    Serial,
+    PortPath,
+    Topology,
+}
+
+impl fmt::Display for Criterion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Vid => "vid",
+            Self::Pid => "pid",
+            Self::Serial => "serial",
+            Self::PortPath => "port_path",
+            Self::Topology => "topology",
+        };
+        f.write_str(s)
+    }
+}
+
+/// 单个条件的比对结果 (期望值来自规则，实际值来自设备)
+#[derive(Debug, Clone, Serialize)]
+pub struct CriterionCheck {
+    pub criterion: Criterion,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    pub passed: bool,
+}
+
+/// 一条规则对某个设备的匹配情况
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleExplanation {
+    pub role: String,
+    pub strategy: BindStrategy,
+    pub priority: i32,
+    // 所有条件都通过
+    pub matched: bool,
+    // 最终生效的规则 (命中且优先级最高、没有冲突)
+    pub selected: bool,
+    pub checks: Vec<CriterionCheck>,
+}
+
+/// `explain` 的结果：设备当前的匹配结果 + 每条规则的逐项比对
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceExplanation {
+    pub device: DeviceView,
+    pub rules: Vec<RuleExplanation>,
+}
+
+/// 规则预览 (dry-run)：候选规则集在当前在线设备上的匹配结果，不保存
+#[derive(Debug, Clone, Serialize)]
+pub struct RulesPreview {
+    pub devices: Vec<DeviceView>,
+    // 当前已绑定、应用候选规则后会失去绑定的角色
+    pub unbound: Vec<String>,
+    // 当前未绑定、应用后会绑定的角色
+    pub bound: Vec<String>,
+    // 应用后会换到另一个设备的角色
+    pub moved: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+    // 候选规则本身不合法时的原因 (保存时会被拒绝)
+    pub invalid: Option<String>,
+}
+
+/// 以角色为中心的状态：每条规则是否在线
+#[derive(Debug, Clone, Serialize)]
+pub struct RoleStatus {
+    pub role: String,
+    pub required: bool,
+    pub online: bool,
+    // 最后一次在线的时间 (Unix 毫秒)；在线时为当前时间，服务启动后从未出现过时为空
+    pub last_seen_ms: Option<u64>,
+    pub system_path: Option<String>,
+    pub device: Option<DeviceView>,
+    // 导致角色无法绑定的冲突
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Serializable copy of `usb_resolver::RawDeviceInfo` (which implements neither Serialize nor Deserialize)
+// `usb_resolver::RawDeviceInfo` 的可序列化副本 (原类型没有实现 Serialize/Deserialize)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawDevice {
+    pub vid: u16,
+    pub pid: u16,
+    pub serial: Option<String>,
+    pub port_path: String,
+    pub system_path: String,
+    pub system_path_alt: Option<String>,
+}
+
+impl From<RawDeviceInfo> for RawDevice {
+    fn from(value: RawDeviceInfo) -> Self {
+        Self {
+            vid: value.vid,
+            pid: value.pid,
+            serial: value.serial,
             port_path: value.port_path,
             system_path: value.system_path,
+            system_path_alt: value.system_path_alt,
         }
     }
 }
+
+/// 角色解析结果：节点真正要打开的路径 + 匹配到的原始设备信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolvedRole {
+    pub role: String,
+    // 优先使用设备节点 (Linux 下的 /dev/ttyUSB0 等)，没有时退回 system_path
+    pub path: String,
+    pub system_path: String,
+    pub device: RawDevice,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> DeviceConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn old_rules_without_strategy_infer_it_from_fields() {
+        let rule = parse(r#"{"role":"a","vid":1,"pid":2,"serial":"SN","port_path":"p"}"#);
+        assert_eq!(rule.strategy, BindStrategy::PortSerial);
+
+        let rule = parse(r#"{"role":"a","vid":1,"pid":2,"serial":null,"port_path":"p"}"#);
+        assert_eq!(rule.strategy, BindStrategy::Port);
+
+        let rule = parse(r#"{"role":"a","vid":1,"pid":2,"serial":"SN"}"#);
+        assert_eq!(rule.strategy, BindStrategy::Serial);
+
+        let rule = parse(r#"{"role":"a","vid":1,"pid":2}"#);
+        assert_eq!(rule.strategy, BindStrategy::VidPid);
+        assert_eq!(rule.priority, 0);
+        assert!(!rule.required);
+        assert!(rule.topology.is_none());
+    }
+
+    #[test]
+    fn topology_anchor_implies_topology_strategy() {
+        let rule = parse(
+            r#"{"role":"a","vid":1,"pid":2,
+                "topology":{"root":{"kind":"controller","path":"0000:00:14.0"},"chain":"2"}}"#,
+        );
+        assert_eq!(rule.strategy, BindStrategy::Topology);
+    }
+
+    #[test]
+    fn empty_strings_count_as_missing() {
+        let rule = parse(r#"{"role":"a","vid":1,"pid":2,"serial":"","port_path":""}"#);
+        assert_eq!(rule.serial, None);
+        assert_eq!(rule.port_path, None);
+        assert_eq!(rule.strategy, BindStrategy::VidPid);
+    }
+
+    #[test]
+    fn explicit_strategy_is_kept() {
+        let rule = parse(
+            r#"{"role":"a","vid":1,"pid":2,"strategy":"serial","serial":"SN","port_path":"p"}"#,
+        );
+        assert_eq!(rule.strategy, BindStrategy::Serial);
+    }
+
+    #[test]
+    fn default_fields_are_not_written_back() {
+        let rule = parse(r#"{"role":"a","vid":1,"pid":2,"serial":"SN"}"#);
+        let json = serde_json::to_value(&rule).unwrap();
+        let object = json.as_object().unwrap();
+        assert!(!object.contains_key("priority"));
+        assert!(!object.contains_key("required"));
+        assert!(!object.contains_key("topology"));
+
+        let round_trip: DeviceConfig = serde_json::from_value(json).unwrap();
+        assert_eq!(round_trip, rule);
+    }
+
+    #[test]
+    fn precedence_orders_priority_then_specificity() {
+        let mut vid_pid = parse(r#"{"role":"a","vid":1,"pid":2}"#);
+        let port_serial = parse(r#"{"role":"b","vid":1,"pid":2,"serial":"SN","port_path":"p"}"#);
+        assert!(port_serial.precedence() > vid_pid.precedence());
+
+        vid_pid.priority = 1;
+        assert!(vid_pid.precedence() > port_serial.precedence());
+    }
+
+    #[test]
+    fn controller_anchor_round_trips_with_root_hub() {
+        let root: AnchorRoot = "controller=0000:00:14.0#1".parse().unwrap();
+        assert_eq!(
+            root,
+            AnchorRoot::Controller {
+                path: "0000:00:14.0".to_string(),
+                root_hub: Some(1),
+            }
+        );
+        let anchor = TopologyAnchor {
+            root,
+            chain: "2.1".to_string(),
+        };
+        assert_eq!(anchor.to_string(), "controller=0000:00:14.0#1/2.1");
+
+        assert!("controller=0000:00:14.0#x".parse::<AnchorRoot>().is_err());
+        assert!("controller=#1".parse::<AnchorRoot>().is_err());
+    }
+
+    #[test]
+    fn old_controller_anchor_has_no_root_hub() {
+        let rule = parse(
+            r#"{"role":"a","vid":1,"pid":2,
+                "topology":{"root":{"kind":"controller","path":"0000:00:14.0"},"chain":"2"}}"#,
+        );
+        let anchor = rule.topology.unwrap();
+        assert!(matches!(
+            anchor.root,
+            AnchorRoot::Controller { root_hub: None, .. }
+        ));
+        assert_eq!(anchor.to_string(), "controller=0000:00:14.0/2");
+
+        let json = serde_json::to_string(&anchor).unwrap();
+        assert!(!json.contains("root_hub"));
+    }
+}
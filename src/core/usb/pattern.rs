@@ -0,0 +1,127 @@
+//! Glob patterns for `port_path` and topology chains: `*` matches any run of characters, `?` exactly one
+//! On Linux `port_path` is the udev `ID_PATH`, e.g. `pci-0000:00:14.0-usb-0:2.1:1.0`, so patterns use that format:
+//! `pci-0000:00:14.0-usb-0:2.*` (anything behind the hub on port 2) or `*-usb-0:1.4:1.0` (port 1.4 on any controller)
+// port_path 和拓扑端口链的通配符匹配：`*` 匹配任意长度字符，`?` 匹配单个字符
+// Linux 下 port_path 是 udev 的 ID_PATH (`doratool list` 里显示的值)，通配符也按这个格式写：
+// 例如 `pci-0000:00:14.0-usb-0:2.*` (2 号口后面的 Hub 上的任意设备)、`*-usb-0:1.4:1.0` (任意控制器的 1.4 口)
+// 拓扑端口链 (`2.1`) 使用同一套规则，例如 `2.*`
+
+/// 字符串是否包含通配符 (不含通配符时按精确比较)
+pub fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// 通配符匹配 (贪心 + 回溯到上一个 `*`，线性复杂度)
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // 上一个 `*` 的位置，以及它当时对应的文本位置
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // 让上一个 `*` 多吞一个字符再试
+                Some((sp, st)) => {
+                    p = sp + 1;
+                    t = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    // 文本用完后，剩下的只能是 `*`
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_patterns() {
+        assert!(is_pattern("pci-0000:00:14.0-usb-0:2.*"));
+        assert!(is_pattern("2.?"));
+        assert!(!is_pattern("pci-0000:00:14.0-usb-0:2:1.0"));
+    }
+
+    #[test]
+    fn literal_patterns_match_exactly() {
+        assert!(glob_match("1-2.4", "1-2.4"));
+        assert!(!glob_match("1-2.4", "1-2.41"));
+        assert!(!glob_match("1-2.4", "1-2."));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "x"));
+    }
+
+    #[test]
+    fn star_matches_any_run_including_empty() {
+        assert!(glob_match("2.*", "2.4"));
+        assert!(glob_match("2.*", "2.4.1"));
+        assert!(glob_match("2.*", "2."));
+        assert!(!glob_match("2.*", "3.4"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("**", "anything"));
+    }
+
+    #[test]
+    fn question_mark_matches_exactly_one_char() {
+        assert!(glob_match("2.?", "2.4"));
+        assert!(!glob_match("2.?", "2."));
+        assert!(!glob_match("2.?", "2.41"));
+    }
+
+    // 文档中的例子，按 udev ID_PATH 的真实格式
+    #[test]
+    fn matches_udev_id_path_behind_a_hub_port() {
+        let pattern = "pci-0000:00:14.0-usb-0:2.*";
+        assert!(glob_match(pattern, "pci-0000:00:14.0-usb-0:2.1:1.0"));
+        assert!(glob_match(pattern, "pci-0000:00:14.0-usb-0:2.4.3:1.0"));
+        // 直接插在 2 号口上的是 Hub 本身，不是它后面的设备
+        assert!(!glob_match(pattern, "pci-0000:00:14.0-usb-0:2:1.0"));
+        assert!(!glob_match(pattern, "pci-0000:00:14.0-usb-0:3.1:1.0"));
+        assert!(!glob_match(pattern, "pci-0000:00:1a.0-usb-0:2.1:1.0"));
+    }
+
+    #[test]
+    fn matches_udev_id_path_on_any_controller() {
+        let pattern = "*-usb-0:1.4:1.0";
+        assert!(glob_match(pattern, "pci-0000:00:14.0-usb-0:1.4:1.0"));
+        assert!(glob_match(pattern, "pci-0000:00:1a.0-usb-0:1.4:1.0"));
+        // 树莓派 4 的 VL805 控制器
+        assert!(glob_match(
+            pattern,
+            "platform-fd500000.pcie-pci-0000:01:00.0-usb-0:1.4:1.0"
+        ));
+        assert!(!glob_match(pattern, "pci-0000:00:14.0-usb-0:1.4.2:1.0"));
+        assert!(!glob_match(pattern, "pci-0000:00:14.0-usb-0:1.4:1.1"));
+        assert!(!glob_match(pattern, "N/A"));
+    }
+
+    #[test]
+    fn backtracks_to_the_last_star() {
+        // 第一次尝试让 `*` 只吞 "a"，失败后需要回溯
+        assert!(glob_match("*ab", "aab"));
+        assert!(glob_match("a*b*c", "axxbyybzc"));
+        assert!(!glob_match("a*b*c", "axxbyyb"));
+        assert!(glob_match("*.1.*", "pci-0000:00:14.0-usb-0:2.1.3:1.0"));
+    }
+
+    #[test]
+    fn matches_non_ascii_by_char() {
+        assert!(glob_match("摄像头?", "摄像头1"));
+        assert!(!glob_match("摄像头?", "摄像头"));
+    }
+}
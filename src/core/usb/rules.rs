@@ -0,0 +1,299 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{
+    core::usb::{
+        models::{BindStrategy, DeviceConfig, DeviceView},
+        service,
+    },
+    infra::{config, state::AppState},
+};
+
+/// 规则修改失败的原因
+#[derive(Debug)]
+pub enum RuleError {
+    // 角色已存在
+    Conflict(String),
+    // 角色不存在
+    NotFound(String),
+    // 设备不在线
+    DeviceNotFound(String),
+    // 规则本身不合法
+    Invalid(String),
+    // 持久化失败
+    Storage(anyhow::Error),
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Conflict(role) => write!(f, "role '{}' already exists", role),
+            Self::NotFound(role) => write!(f, "role '{}' not found", role),
+            Self::DeviceNotFound(id) => write!(f, "device '{}' not found", id),
+            Self::Invalid(msg) => write!(f, "invalid rule: {}", msg),
+            Self::Storage(e) => write!(f, "failed to save rules: {:#}", e),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// 在当前规则的副本上执行修改，校验并持久化成功后再替换内存中的规则
+/// 同一时间只允许一个修改 (rules_update 锁)，避免并发请求互相覆盖
+pub fn modify_rules<T, F>(state: &AppState, modify: F) -> Result<T, RuleError>
+where
+    F: FnOnce(&mut Vec<DeviceConfig>) -> Result<T, RuleError>,
+{
+    let _guard = state.rules_update.lock().unwrap();
+
+    let mut rules = state.rules.read().unwrap().clone();
+    let output = modify(&mut rules)?;
+
+    service::validate_rules(&rules).map_err(|e| RuleError::Invalid(e.to_string()))?;
+    config::save_rules(&state.config_path, &rules).map_err(RuleError::Storage)?;
+
+    state.set_rules(rules);
+    Ok(output)
+}
+
+/// 新增一条规则，角色必须唯一
+pub fn add_rule(state: &AppState, rule: DeviceConfig) -> Result<(), RuleError> {
+    modify_rules(state, |rules| {
+        if rules.iter().any(|r| r.role == rule.role) {
+            return Err(RuleError::Conflict(rule.role));
+        }
+        rules.push(rule);
+        Ok(())
+    })
+}
+
+/// 删除某个角色的规则，返回被删除的规则
+pub fn remove_rule(state: &AppState, role: &str) -> Result<DeviceConfig, RuleError> {
+    modify_rules(state, |rules| {
+        let index = rules
+            .iter()
+            .position(|r| r.role == role)
+            .ok_or_else(|| RuleError::NotFound(role.to_string()))?;
+        Ok(rules.remove(index))
+    })
+}
+
+/// 查询某个角色的规则
+pub fn get_rule(state: &AppState, role: &str) -> Result<DeviceConfig, RuleError> {
+    state
+        .rules
+        .read()
+        .unwrap()
+        .iter()
+        .find(|r| r.role == role)
+        .cloned()
+        .ok_or_else(|| RuleError::NotFound(role.to_string()))
+}
+
+/// 更新某个角色的规则
+/// 允许通过 `rule.role` 改名，但新名字不能和其它角色重复
+pub fn update_rule(state: &AppState, role: &str, rule: DeviceConfig) -> Result<(), RuleError> {
+    modify_rules(state, |rules| {
+        let index = rules
+            .iter()
+            .position(|r| r.role == role)
+            .ok_or_else(|| RuleError::NotFound(role.to_string()))?;
+
+        if rule.role != role && rules.iter().any(|r| r.role == rule.role) {
+            return Err(RuleError::Conflict(rule.role));
+        }
+
+        rules[index] = rule;
+        Ok(())
+    })
+}
+
+/// 整体替换规则列表 (允许为空，用于清空所有规则)
+pub fn replace_rules(state: &AppState, new_rules: Vec<DeviceConfig>) -> Result<(), RuleError> {
+    modify_rules(state, |rules| {
+        let mut roles = HashSet::new();
+        if let Some(dup) = new_rules.iter().find(|r| !roles.insert(r.role.as_str())) {
+            return Err(RuleError::Conflict(dup.role.clone()));
+        }
+
+        *rules = new_rules;
+        Ok(())
+    })
+}
+
+/// 把一个在线设备绑定到角色
+/// 规则由服务端根据 RawDeviceInfo 生成 (见 `service::rule_for_device`)，返回绑定后的设备视图
+pub fn bind_device(
+    state: &AppState,
+    device_id: &str,
+    role: &str,
+    strategy: Option<BindStrategy>,
+) -> Result<DeviceView, RuleError> {
+    let devices = state.live_devices.read().unwrap().clone();
+    let raw = service::find_device(&devices, device_id)
+        .cloned()
+        .ok_or_else(|| RuleError::DeviceNotFound(device_id.to_string()))?;
+
+    let rule = service::rule_for_device(&raw, &devices, role, strategy)
+        .map_err(|e| RuleError::Invalid(e.to_string()))?;
+    add_rule(state, rule)?;
+
+    // 按完整的在线设备列表重新匹配，返回的视图会带上可能出现的冲突
+    let rules = state.rules.read().unwrap().clone();
+    let devices = state.live_devices.read().unwrap().clone();
+    let view = service::match_raw_to_views(&devices, &rules)
+        .into_iter()
+        .find(|view| view.id == device_id)
+        .unwrap_or_else(|| DeviceView::new(raw, &devices));
+    Ok(view)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, process};
+
+    use super::*;
+
+    // 测试用的临时目录，离开作用域时删除
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("doratool-rules-{}-{}", process::id(), name));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn rule(role: &str, pid: u16) -> DeviceConfig {
+        DeviceConfig {
+            role: role.to_string(),
+            vid: 0x1a86,
+            pid,
+            strategy: BindStrategy::VidPid,
+            serial: None,
+            port_path: None,
+            topology: None,
+            priority: 0,
+            required: false,
+        }
+    }
+
+    fn state(dir: &TempDir, rules: Vec<DeviceConfig>) -> AppState {
+        let path = dir.0.join("usb_rules.json");
+        config::save_rules(&path, &rules).unwrap();
+        AppState::new(path, rules)
+    }
+
+    fn roles(state: &AppState) -> Vec<String> {
+        state
+            .rules
+            .read()
+            .unwrap()
+            .iter()
+            .map(|r| r.role.clone())
+            .collect()
+    }
+
+    fn saved_roles(state: &AppState) -> Vec<String> {
+        config::load_rules(&state.config_path)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.role)
+            .collect()
+    }
+
+    #[test]
+    fn add_rule_saves_and_rejects_duplicate_role() {
+        let dir = TempDir::new("add");
+        let state = state(&dir, vec![rule("cam", 1)]);
+
+        add_rule(&state, rule("arm", 2)).unwrap();
+        assert_eq!(roles(&state), ["cam", "arm"]);
+        assert_eq!(saved_roles(&state), ["cam", "arm"]);
+
+        let err = add_rule(&state, rule("cam", 3)).unwrap_err();
+        assert!(matches!(&err, RuleError::Conflict(role) if role == "cam"));
+        assert_eq!(saved_roles(&state), ["cam", "arm"]);
+    }
+
+    #[test]
+    fn update_and_remove_missing_role_is_not_found() {
+        let dir = TempDir::new("missing");
+        let state = state(&dir, vec![rule("cam", 1)]);
+
+        let err = update_rule(&state, "arm", rule("arm", 2)).unwrap_err();
+        assert!(matches!(&err, RuleError::NotFound(role) if role == "arm"));
+        let err = remove_rule(&state, "arm").unwrap_err();
+        assert!(matches!(&err, RuleError::NotFound(role) if role == "arm"));
+        assert!(matches!(
+            get_rule(&state, "arm"),
+            Err(RuleError::NotFound(_))
+        ));
+
+        assert_eq!(remove_rule(&state, "cam").unwrap().role, "cam");
+        assert!(roles(&state).is_empty());
+    }
+
+    #[test]
+    fn update_rule_renames_but_not_onto_another_role() {
+        let dir = TempDir::new("rename");
+        let state = state(&dir, vec![rule("cam", 1), rule("arm", 2)]);
+
+        let err = update_rule(&state, "cam", rule("arm", 1)).unwrap_err();
+        assert!(matches!(&err, RuleError::Conflict(role) if role == "arm"));
+
+        update_rule(&state, "cam", rule("top_cam", 1)).unwrap();
+        assert_eq!(saved_roles(&state), ["top_cam", "arm"]);
+    }
+
+    #[test]
+    fn replace_with_empty_list_clears_all_rules() {
+        let dir = TempDir::new("clear");
+        let state = state(&dir, vec![rule("cam", 1), rule("arm", 2)]);
+
+        replace_rules(&state, Vec::new()).unwrap();
+        assert!(roles(&state).is_empty());
+        assert!(saved_roles(&state).is_empty());
+        assert_eq!(fs::read_to_string(&state.config_path).unwrap().trim(), "[]");
+    }
+
+    #[test]
+    fn replace_rejects_duplicate_roles() {
+        let dir = TempDir::new("replace_dup");
+        let state = state(&dir, vec![rule("cam", 1)]);
+
+        let err = replace_rules(&state, vec![rule("arm", 2), rule("arm", 3)]).unwrap_err();
+        assert!(matches!(&err, RuleError::Conflict(role) if role == "arm"));
+        assert_eq!(roles(&state), ["cam"]);
+    }
+
+    #[test]
+    fn invalid_or_unsaved_rules_leave_state_unchanged() {
+        let dir = TempDir::new("invalid");
+        let state = state(&dir, vec![rule("cam", 1)]);
+
+        // Serial 策略缺少 serial
+        let mut invalid = rule("arm", 2);
+        invalid.strategy = BindStrategy::Serial;
+        assert!(matches!(
+            add_rule(&state, invalid),
+            Err(RuleError::Invalid(_))
+        ));
+        assert_eq!(roles(&state), ["cam"]);
+
+        // 保存失败 (目录不存在) 时不替换内存中的规则
+        let unsaved = AppState::new(dir.0.join("missing/usb_rules.json"), vec![rule("cam", 1)]);
+        assert!(matches!(
+            add_rule(&unsaved, rule("arm", 2)),
+            Err(RuleError::Storage(_))
+        ));
+        assert_eq!(roles(&unsaved), ["cam"]);
+    }
+}
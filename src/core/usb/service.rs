@@ -1,4 +1,18 @@
-use crate::core::usb::models::{DeviceConfig, DeviceView};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::{Result, bail};
+
+use crate::core::usb::{
+    diff::DeviceKey,
+    env,
+    models::{
+        AnchorRoot, BindStrategy, Conflict, Criterion, CriterionCheck, DeviceConfig,
+        DeviceExplanation, DeviceView, ResolvedRole, RoleStatus, RuleExplanation, RulesPreview,
+        TopologyAnchor,
+    },
+    pattern,
+    topology::Topology,
+};
 use usb_resolver::RawDeviceInfo; // 假设 RawDeviceInfo 在这里可用，或者从 models 引入
 
 /// 纯业务逻辑：将“原始设备数据”与“配置规则”进行匹配，生成“视图数据”
@@ -9,42 +23,984 @@ pub fn match_raw_to_views(
     raw_devices: &[RawDeviceInfo],
     rules: &[DeviceConfig],
 ) -> Vec<DeviceView> {
+    let conflicts = detect_conflicts(raw_devices, rules);
     let mut views = Vec::new();
 
     // 遍历传入的原始设备快照
     for raw in raw_devices {
-        let mut matched_role = None;
-
-        // O(M*N) 的匹配逻辑 (通常 M, N 很小，完全没问题)
-        for rule in rules {
-            // 1. 硬件 ID 匹配 (u16 比对)
-            if raw.vid == rule.vid && raw.pid == rule.pid {
-                // 2. 序列号匹配 (如果规则配了 SN)
-                let serial_match = match (&rule.serial, &raw.serial) {
-                    (Some(r), Some(v)) => r == v,
-                    (None, _) => true, // 规则没配 SN，视为匹配
-                    _ => false,        // 规则配了但设备没有，不匹配
-                };
-
-                // 3. 物理路径匹配 (如果规则配了 Path)
-                let path_match = rule.port_path == raw.port_path;
-
-                // 4. 判定
-                if serial_match && path_match {
-                    matched_role = Some(rule.role.clone());
-                    break; // 找到规则，跳出内层循环
-                }
-            }
-        }
-
         // --- 转换逻辑 ---
         // 利用之前在 models.rs 实现的 From<RawDeviceInfo>
         // 注意：这里可能需要 raw.clone()，因为 DeviceView 拥有数据的所有权
-        let mut view = DeviceView::from(raw.clone());
-        view.role = matched_role;
+        let mut view = DeviceView::new(raw.clone(), raw_devices);
+        view.conflicts = conflicts
+            .iter()
+            .filter(|c| c.involves(&view.id))
+            .cloned()
+            .collect();
+
+        // 有冲突的设备不绑定任何角色，避免每次启动解析到不同的端口
+        if view.conflicts.is_empty() {
+            // O(M*N) 的匹配逻辑 (通常 M, N 很小，完全没问题)
+            view.role = match_rule(raw, rules, raw_devices).map(|rule| rule.role.clone());
+        }
 
         views.push(view);
     }
 
     views
 }
+
+/// 冲突报告：
+/// - 一个设备命中多条同等优先级的规则 (RuleTie)
+/// - 一个角色的规则命中多个在线设备 (AmbiguousRole)
+pub fn detect_conflicts(raw_devices: &[RawDeviceInfo], rules: &[DeviceConfig]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let mut devices_by_role: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+
+    for raw in raw_devices {
+        let id = DeviceKey::of(raw).to_string();
+        match resolve_rule(raw, rules, raw_devices) {
+            RuleMatch::Unique(rule) => devices_by_role.entry(&rule.role).or_default().push(id),
+            RuleMatch::Tie(tied) => conflicts.push(Conflict::RuleTie {
+                device: id,
+                roles: tied.iter().map(|r| r.role.clone()).collect(),
+            }),
+            RuleMatch::None => {}
+        }
+    }
+
+    for (role, devices) in devices_by_role {
+        if devices.len() > 1 {
+            conflicts.push(Conflict::AmbiguousRole {
+                role: role.to_string(),
+                devices,
+            });
+        }
+    }
+
+    conflicts
+}
+
+/// 当前已绑定的角色 -> 设备视图
+/// 用于对比变更前后，找出哪些角色上线/下线
+pub fn role_bindings(
+    raw_devices: &[RawDeviceInfo],
+    rules: &[DeviceConfig],
+) -> BTreeMap<String, DeviceView> {
+    bindings_of(&match_raw_to_views(raw_devices, rules))
+}
+
+/// 从已经算好的视图中取出角色绑定
+pub fn bindings_of(views: &[DeviceView]) -> BTreeMap<String, DeviceView> {
+    views
+        .iter()
+        .filter_map(|view| view.role.clone().map(|role| (role, view.clone())))
+        .collect()
+}
+
+/// 预览候选规则集：对比当前规则和候选规则在同一批在线设备上的绑定结果
+pub fn preview_rules(
+    raw_devices: &[RawDeviceInfo],
+    current: &[DeviceConfig],
+    candidate: &[DeviceConfig],
+) -> RulesPreview {
+    let before = role_bindings(raw_devices, current);
+    let devices = match_raw_to_views(raw_devices, candidate);
+    let after = bindings_of(&devices);
+
+    let unbound = before
+        .keys()
+        .filter(|role| !after.contains_key(*role))
+        .cloned()
+        .collect();
+    let bound = after
+        .keys()
+        .filter(|role| !before.contains_key(*role))
+        .cloned()
+        .collect();
+    let moved = after
+        .iter()
+        .filter(|(role, view)| before.get(*role).is_some_and(|old| old.id != view.id))
+        .map(|(role, _)| role.clone())
+        .collect();
+
+    RulesPreview {
+        conflicts: detect_conflicts(raw_devices, candidate),
+        invalid: validate_rules(candidate).err().map(|e| e.to_string()),
+        devices,
+        unbound,
+        bound,
+        moved,
+    }
+}
+
+/// 以角色为中心的状态，按规则文件顺序
+/// `last_seen` 来自 `AppState::role_last_seen` (CLI 本地检查时为空)，`now` 用于在线角色
+pub fn role_statuses(
+    raw_devices: &[RawDeviceInfo],
+    rules: &[DeviceConfig],
+    last_seen: &HashMap<String, u64>,
+    now: u64,
+) -> Vec<RoleStatus> {
+    let views = match_raw_to_views(raw_devices, rules);
+    let bindings = bindings_of(&views);
+    let conflicts = detect_conflicts(raw_devices, rules);
+
+    rules
+        .iter()
+        .map(|rule| {
+            let device = bindings.get(&rule.role).cloned();
+            let online = device.is_some();
+            RoleStatus {
+                role: rule.role.clone(),
+                required: rule.required,
+                online,
+                last_seen_ms: if online {
+                    Some(now)
+                } else {
+                    last_seen.get(&rule.role).copied()
+                },
+                system_path: device.as_ref().map(|d| d.system_path.clone()),
+                device,
+                conflicts: conflicts
+                    .iter()
+                    .filter(|c| c.involves_role(&rule.role))
+                    .cloned()
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// 离线的必需角色 (`doratool check` 据此决定退出码)
+pub fn missing_required(statuses: &[RoleStatus]) -> Vec<&str> {
+    statuses
+        .iter()
+        .filter(|s| s.required && !s.online)
+        .map(|s| s.role.as_str())
+        .collect()
+}
+
+/// 解析角色当前对应的设备 (未配置、离线或存在冲突时返回 None)
+pub fn resolve_role(
+    raw_devices: &[RawDeviceInfo],
+    rules: &[DeviceConfig],
+    role: &str,
+) -> Option<ResolvedRole> {
+    let view = match_raw_to_views(raw_devices, rules)
+        .into_iter()
+        .find(|view| view.role.as_deref() == Some(role))?;
+    resolved_from_view(raw_devices, role, &view)
+}
+
+/// 解析所有已绑定的角色，按规则文件顺序 (离线的角色不包含在内)
+pub fn resolve_roles(raw_devices: &[RawDeviceInfo], rules: &[DeviceConfig]) -> Vec<ResolvedRole> {
+    let bindings = role_bindings(raw_devices, rules);
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let view = bindings.get(&rule.role)?;
+            resolved_from_view(raw_devices, &rule.role, view)
+        })
+        .collect()
+}
+
+fn resolved_from_view(
+    raw_devices: &[RawDeviceInfo],
+    role: &str,
+    view: &DeviceView,
+) -> Option<ResolvedRole> {
+    let raw = find_device(raw_devices, &view.id)?.clone();
+
+    Some(ResolvedRole {
+        role: role.to_string(),
+        path: raw
+            .system_path_alt
+            .clone()
+            .unwrap_or_else(|| raw.system_path.clone()),
+        system_path: raw.system_path.clone(),
+        device: raw.into(),
+    })
+}
+
+/// 单个设备的规则匹配结果
+#[derive(Debug)]
+pub enum RuleMatch<'a> {
+    // 没有规则命中
+    None,
+    // 唯一胜出的规则
+    Unique(&'a DeviceConfig),
+    // 多条规则命中且优先级、具体程度完全相同，无法决定
+    Tie(Vec<&'a DeviceConfig>),
+}
+
+impl<'a> RuleMatch<'a> {
+    pub fn winner(&self) -> Option<&'a DeviceConfig> {
+        match self {
+            Self::Unique(rule) => Some(rule),
+            Self::None | Self::Tie(_) => None,
+        }
+    }
+}
+
+/// 为单个设备选出生效的规则
+/// 先比显式优先级 (priority)，再比具体程度 (见 `BindStrategy::specificity`)，与文件顺序无关
+/// `devices` 是同一时刻的在线设备列表，Hub 锚点需要在里面查找上游 Hub
+pub fn resolve_rule<'a>(
+    raw: &RawDeviceInfo,
+    rules: &'a [DeviceConfig],
+    devices: &[RawDeviceInfo],
+) -> RuleMatch<'a> {
+    let matched: Vec<&DeviceConfig> = rules
+        .iter()
+        .filter(|rule| rule_matches(rule, raw, devices))
+        .collect();
+
+    let Some(best) = matched.iter().map(|rule| rule.precedence()).max() else {
+        return RuleMatch::None;
+    };
+
+    let mut top: Vec<&DeviceConfig> = matched
+        .into_iter()
+        .filter(|rule| rule.precedence() == best)
+        .collect();
+
+    if top.len() == 1 {
+        RuleMatch::Unique(top.remove(0))
+    } else {
+        RuleMatch::Tie(top)
+    }
+}
+
+/// 为单个设备查找生效的规则，出现平局时不绑定任何角色
+/// match_raw_to_views 和 USB 事件线程共用这一套匹配逻辑
+pub fn match_rule<'a>(
+    raw: &RawDeviceInfo,
+    rules: &'a [DeviceConfig],
+    devices: &[RawDeviceInfo],
+) -> Option<&'a DeviceConfig> {
+    resolve_rule(raw, rules, devices).winner()
+}
+
+/// 判断单条规则是否命中设备 (按规则的绑定策略)
+pub fn rule_matches(rule: &DeviceConfig, raw: &RawDeviceInfo, devices: &[RawDeviceInfo]) -> bool {
+    check_rule(rule, raw, devices)
+        .iter()
+        .all(|check| check.passed)
+}
+
+/// 逐项比对规则和设备，只包含规则的绑定策略要求的条件
+/// rule_matches 和 explain 共用这一份逻辑
+pub fn check_rule(
+    rule: &DeviceConfig,
+    raw: &RawDeviceInfo,
+    devices: &[RawDeviceInfo],
+) -> Vec<CriterionCheck> {
+    let hex = |id: u16| Some(format!("0x{:04x}", id));
+
+    // 1. 硬件 ID 匹配 (u16 比对)，所有策略都要求
+    let mut checks = vec![
+        CriterionCheck {
+            criterion: Criterion::Vid,
+            expected: hex(rule.vid),
+            actual: hex(raw.vid),
+            passed: raw.vid == rule.vid,
+        },
+        CriterionCheck {
+            criterion: Criterion::Pid,
+            expected: hex(rule.pid),
+            actual: hex(raw.pid),
+            passed: raw.pid == rule.pid,
+        },
+    ];
+
+    // 2. 序列号匹配 (策略要求时)
+    if rule.strategy.requires_serial() {
+        checks.push(CriterionCheck {
+            criterion: Criterion::Serial,
+            expected: rule.serial.clone(),
+            actual: raw.serial.clone(),
+            passed: matches!((&rule.serial, &raw.serial), (Some(r), Some(v)) if r == v),
+        });
+    }
+
+    // 3. 物理路径匹配 (策略要求时)，规则里可以写通配符
+    if rule.strategy.requires_port() {
+        checks.push(CriterionCheck {
+            criterion: Criterion::PortPath,
+            expected: rule.port_path.clone(),
+            actual: Some(raw.port_path.clone()),
+            passed: rule
+                .port_path
+                .as_deref()
+                .is_some_and(|p| port_matches(p, &raw.port_path)),
+        });
+    }
+
+    // 4. 拓扑匹配 (策略要求时)
+    if rule.strategy.requires_topology() {
+        checks.push(check_topology(rule.topology.as_ref(), raw, devices));
+    }
+
+    checks
+}
+
+/// 拓扑锚点比对：找到锚点 (控制器或上游 Hub)，再比较相对端口链
+fn check_topology(
+    anchor: Option<&TopologyAnchor>,
+    raw: &RawDeviceInfo,
+    devices: &[RawDeviceInfo],
+) -> CriterionCheck {
+    let device = Topology::parse(&raw.system_path, devices);
+
+    // 设备相对锚点的端口链 (找不到锚点时为 None)
+    let actual = match (anchor, &device) {
+        (Some(anchor), Some(device)) => match &anchor.root {
+            AnchorRoot::Controller { path, root_hub } => (&device.controller == path
+                && root_hub.is_none_or(|n| n == device.root_hub))
+            .then(|| device.chain.clone()),
+            AnchorRoot::Hub { vid, pid, serial } => {
+                let relatives: Vec<String> = devices
+                    .iter()
+                    .filter(|hub| hub.vid == *vid && hub.pid == *pid)
+                    .filter(|hub| serial.is_none() || hub.serial == *serial)
+                    .filter_map(|hub| {
+                        device.relative_to(&Topology::parse(&hub.system_path, devices)?)
+                    })
+                    .collect();
+                // 同型号的 Hub 可能有多个，只要有一个链路对得上即可 (多设备命中会被冲突检测发现)
+                relatives
+                    .iter()
+                    .find(|relative| chain_matches(&anchor.chain, relative))
+                    .or(relatives.first())
+                    .cloned()
+            }
+        },
+        _ => None,
+    };
+
+    let passed = match (anchor, &actual) {
+        (Some(anchor), Some(actual)) => chain_matches(&anchor.chain, actual),
+        _ => false,
+    };
+
+    CriterionCheck {
+        criterion: Criterion::Topology,
+        expected: anchor.map(|a| a.to_string()),
+        actual: match (anchor, actual) {
+            (Some(anchor), Some(actual)) => Some(
+                TopologyAnchor {
+                    root: anchor.root.clone(),
+                    chain: actual,
+                }
+                .to_string(),
+            ),
+            // 锚点不存在 / 设备不在锚点下面时，给出设备自己的拓扑方便排查
+            _ => device.map(|d| d.anchor().to_string()),
+        },
+        passed,
+    }
+}
+
+/// 相对端口链比较，同样支持通配符
+fn chain_matches(rule_chain: &str, chain: &str) -> bool {
+    if pattern::is_pattern(rule_chain) {
+        pattern::glob_match(rule_chain, chain)
+    } else {
+        rule_chain == chain
+    }
+}
+
+/// 端口路径比较：含通配符时按 glob 匹配，否则精确比较
+/// 平台拿不到端口路径 ("N/A") 时，通配符也不应该命中
+fn port_matches(rule_port: &str, port_path: &str) -> bool {
+    if pattern::is_pattern(rule_port) {
+        port_path != "N/A" && pattern::glob_match(rule_port, port_path)
+    } else {
+        rule_port == port_path
+    }
+}
+
+/// 解释某个设备为什么绑定 (或没有绑定) 到角色
+/// 设备视图来自完整的 match_raw_to_views (包含冲突)，每条规则按文件顺序逐项比对
+pub fn explain_device(
+    raw_devices: &[RawDeviceInfo],
+    rules: &[DeviceConfig],
+    device_id: &str,
+) -> Option<DeviceExplanation> {
+    let raw = find_device(raw_devices, device_id)?;
+    let device = match_raw_to_views(raw_devices, rules)
+        .into_iter()
+        .find(|view| view.id == device_id)?;
+
+    let rules = rules
+        .iter()
+        .map(|rule| {
+            let checks = check_rule(rule, raw, raw_devices);
+            RuleExplanation {
+                role: rule.role.clone(),
+                strategy: rule.strategy,
+                priority: rule.priority,
+                matched: checks.iter().all(|check| check.passed),
+                selected: device.role.as_deref() == Some(rule.role.as_str()),
+                checks,
+            }
+        })
+        .collect();
+
+    Some(DeviceExplanation { device, rules })
+}
+
+/// 校验规则列表：角色名不能为空且必须唯一，绑定策略要求的字段必须填写，
+/// 不能有两个角色对应同一个 `DORA_ROLE_*` 环境变量 (例如 `left-arm` 和 `left_arm`)，
+/// 且不能有两条匹配条件和优先级完全相同的规则 (它们在任何设备上都会平局)
+pub fn validate_rules(rules: &[DeviceConfig]) -> Result<()> {
+    let mut roles = HashSet::new();
+    for (index, rule) in rules.iter().enumerate() {
+        if rule.role.trim().is_empty() {
+            bail!("规则的角色名不能为空");
+        }
+        if !roles.insert(rule.role.as_str()) {
+            bail!("角色名重复: {}", rule.role);
+        }
+        if rule.strategy.requires_port() && rule.port_path.is_none() {
+            bail!(
+                "角色 {} 的绑定策略为 {}，但没有填写 port_path",
+                rule.role,
+                rule.strategy
+            );
+        }
+        if rule.strategy.requires_topology() && rule.topology.is_none() {
+            bail!(
+                "角色 {} 的绑定策略为 {}，但没有填写 topology",
+                rule.role,
+                rule.strategy
+            );
+        }
+        if rule.strategy.requires_serial() && rule.serial.is_none() {
+            bail!(
+                "角色 {} 的绑定策略为 {}，但没有填写 serial",
+                rule.role,
+                rule.strategy
+            );
+        }
+        let var = env::env_var_name(&rule.role);
+        if let Some(other) = rules[..index]
+            .iter()
+            .find(|r| env::env_var_name(&r.role) == var)
+        {
+            bail!(
+                "角色 {} 和 {} 对应同一个环境变量 {}",
+                other.role,
+                rule.role,
+                var
+            );
+        }
+        if let Some(other) = rules[..index].iter().find(|r| same_criteria(r, rule)) {
+            bail!(
+                "角色 {} 和 {} 的匹配条件与优先级完全相同，无法决定由谁绑定",
+                other.role,
+                rule.role
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 两条规则的匹配条件 (只看策略要求的字段) 和优先级是否完全相同
+fn same_criteria(a: &DeviceConfig, b: &DeviceConfig) -> bool {
+    a.vid == b.vid
+        && a.pid == b.pid
+        && a.strategy == b.strategy
+        && a.priority == b.priority
+        && (!a.strategy.requires_serial() || a.serial == b.serial)
+        && (!a.strategy.requires_port() || a.port_path == b.port_path)
+        && (!a.strategy.requires_topology() || a.topology == b.topology)
+}
+
+/// 为设备选择默认的绑定策略
+/// 优先使用物理端口 (同型号设备最常见的区分方式)，拿不到端口时退回序列号，都没有时只用 VID/PID
+pub fn default_strategy(raw: &RawDeviceInfo) -> BindStrategy {
+    if has_port_path(raw) {
+        BindStrategy::Port
+    } else if raw.serial.is_some() {
+        BindStrategy::Serial
+    } else {
+        BindStrategy::VidPid
+    }
+}
+
+/// 平台拿不到端口路径时 port_path 为 "N/A"
+fn has_port_path(raw: &RawDeviceInfo) -> bool {
+    !raw.port_path.is_empty() && raw.port_path != "N/A"
+}
+
+/// 根据在线设备的原始信息生成规则
+/// 只填写策略需要的字段，避免前端自己解析十六进制字符串、猜测字段
+/// `devices` 为同一次扫描的完整设备列表 (计算拓扑锚点时使用)
+pub fn rule_for_device(
+    raw: &RawDeviceInfo,
+    devices: &[RawDeviceInfo],
+    role: &str,
+    strategy: Option<BindStrategy>,
+) -> Result<DeviceConfig> {
+    let strategy = strategy.unwrap_or_else(|| default_strategy(raw));
+
+    if strategy.requires_serial() && raw.serial.is_none() {
+        bail!(
+            "设备 {} 没有序列号，无法使用 {} 策略",
+            DeviceKey::of(raw),
+            strategy
+        );
+    }
+    if strategy.requires_port() && !has_port_path(raw) {
+        bail!(
+            "设备 {} 没有端口路径，无法使用 {} 策略",
+            DeviceKey::of(raw),
+            strategy
+        );
+    }
+
+    // 拓扑规则默认锚定在主控制器上
+    let topology = if strategy.requires_topology() {
+        let Some(t) = Topology::parse(&raw.system_path, devices) else {
+            bail!(
+                "设备 {} 无法解析拓扑 (仅支持 Linux sysfs 路径)，无法使用 {} 策略",
+                DeviceKey::of(raw),
+                strategy
+            );
+        };
+        Some(t.anchor())
+    } else {
+        None
+    };
+
+    Ok(DeviceConfig {
+        role: role.to_string(),
+        vid: raw.vid,
+        pid: raw.pid,
+        strategy,
+        serial: raw.serial.clone().filter(|_| strategy.requires_serial()),
+        port_path: Some(raw.port_path.clone()).filter(|_| strategy.requires_port()),
+        topology,
+        priority: 0,
+        required: false,
+    })
+}
+
+/// 按稳定标识 (DeviceKey 字符串，即 DeviceView::id) 查找设备
+pub fn find_device<'a>(raw_devices: &'a [RawDeviceInfo], id: &str) -> Option<&'a RawDeviceInfo> {
+    raw_devices
+        .iter()
+        .find(|raw| DeviceKey::of(raw).to_string() == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PORT: &str = "pci-0000:00:14.0-usb-0:2:1.0";
+
+    fn device(serial: Option<&str>, port_path: &str) -> RawDeviceInfo {
+        RawDeviceInfo {
+            vid: 0x1a86,
+            pid: 0x7523,
+            serial: serial.map(str::to_string),
+            port_path: port_path.to_string(),
+            system_path: "/sys/devices/pci0000:00/0000:00:14.0/usb1/1-2".to_string(),
+            system_path_alt: Some("/dev/ttyUSB0".to_string()),
+        }
+    }
+
+    fn rule(role: &str, strategy: BindStrategy, priority: i32) -> DeviceConfig {
+        DeviceConfig {
+            role: role.to_string(),
+            vid: 0x1a86,
+            pid: 0x7523,
+            strategy,
+            serial: strategy.requires_serial().then(|| "SN1".to_string()),
+            port_path: strategy.requires_port().then(|| PORT.to_string()),
+            topology: None,
+            priority,
+            required: false,
+        }
+    }
+
+    fn winner<'a>(raw: &RawDeviceInfo, rules: &'a [DeviceConfig]) -> Option<&'a str> {
+        match_rule(raw, rules, std::slice::from_ref(raw)).map(|r| r.role.as_str())
+    }
+
+    #[test]
+    fn more_specific_rule_wins_regardless_of_file_order() {
+        let raw = device(Some("SN1"), PORT);
+        let mut rules = vec![
+            rule("any", BindStrategy::VidPid, 0),
+            rule("by_port", BindStrategy::Port, 0),
+            rule("exact", BindStrategy::PortSerial, 0),
+        ];
+        assert_eq!(winner(&raw, &rules), Some("exact"));
+
+        rules.reverse();
+        assert_eq!(winner(&raw, &rules), Some("exact"));
+    }
+
+    #[test]
+    fn priority_beats_specificity() {
+        let raw = device(Some("SN1"), PORT);
+        let rules = vec![
+            rule("exact", BindStrategy::PortSerial, 0),
+            rule("fallback", BindStrategy::VidPid, 10),
+        ];
+        assert_eq!(winner(&raw, &rules), Some("fallback"));
+    }
+
+    #[test]
+    fn equal_precedence_is_a_tie_and_binds_nothing() {
+        let raw = device(None, PORT);
+        let rules = vec![
+            rule("left", BindStrategy::VidPid, 0),
+            rule("right", BindStrategy::VidPid, 0),
+        ];
+
+        let devices = std::slice::from_ref(&raw);
+        match resolve_rule(&raw, &rules, devices) {
+            RuleMatch::Tie(tied) => {
+                let roles: Vec<&str> = tied.iter().map(|r| r.role.as_str()).collect();
+                assert_eq!(roles, vec!["left", "right"]);
+            }
+            _ => panic!("expected a tie"),
+        }
+        assert_eq!(winner(&raw, &rules), None);
+
+        let views = match_raw_to_views(devices, &rules);
+        assert_eq!(views[0].role, None);
+        assert!(matches!(
+            views[0].conflicts.as_slice(),
+            [Conflict::RuleTie { roles, .. }] if roles.len() == 2
+        ));
+    }
+
+    #[test]
+    fn no_matching_rule() {
+        let raw = device(Some("OTHER"), "pci-0000:00:14.0-usb-0:3:1.0");
+        let rules = vec![
+            rule("by_serial", BindStrategy::Serial, 0),
+            rule("by_port", BindStrategy::Port, 0),
+        ];
+        assert!(matches!(
+            resolve_rule(&raw, &rules, std::slice::from_ref(&raw)),
+            RuleMatch::None
+        ));
+    }
+
+    #[test]
+    fn port_patterns_never_match_missing_port_path() {
+        let mut glob = rule("hub_port", BindStrategy::Port, 0);
+        glob.port_path = Some("pci-0000:00:14.0-usb-0:*".to_string());
+
+        assert_eq!(
+            winner(&device(None, PORT), std::slice::from_ref(&glob)),
+            Some("hub_port")
+        );
+        assert_eq!(
+            winner(&device(None, "N/A"), std::slice::from_ref(&glob)),
+            None
+        );
+
+        let mut any = glob.clone();
+        any.port_path = Some("*".to_string());
+        assert_eq!(
+            winner(&device(None, "N/A"), std::slice::from_ref(&any)),
+            None
+        );
+    }
+
+    #[test]
+    fn validate_rejects_roles_with_the_same_env_var() {
+        let mut left = rule("left-arm", BindStrategy::Serial, 0);
+        let mut right = rule("left_arm", BindStrategy::Serial, 0);
+        left.serial = Some("A".to_string());
+        right.serial = Some("B".to_string());
+
+        // 不管设备是否在线，配置本身就是无效的
+        let err = validate_rules(&[left.clone(), right])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("DORA_ROLE_LEFT_ARM"), "{}", err);
+
+        let mut other = rule("right_arm", BindStrategy::Serial, 0);
+        other.serial = Some("B".to_string());
+        assert!(validate_rules(&[left, other]).is_ok());
+    }
+
+    // 同型号 (CH340) 的设备插在 14.0 控制器的第 n 个端口上
+    fn at(n: u32, serial: Option<&str>) -> RawDeviceInfo {
+        RawDeviceInfo {
+            vid: 0x1a86,
+            pid: 0x7523,
+            serial: serial.map(str::to_string),
+            port_path: port(n),
+            system_path: format!("/sys/devices/pci0000:00/0000:00:14.0/usb1/1-{}", n),
+            system_path_alt: Some(format!("/dev/ttyUSB{}", n)),
+        }
+    }
+
+    fn port(n: u32) -> String {
+        format!("pci-0000:00:14.0-usb-0:{}:1.0", n)
+    }
+
+    fn id(raw: &RawDeviceInfo) -> String {
+        DeviceKey::of(raw).to_string()
+    }
+
+    fn by_port(role: &str, n: u32) -> DeviceConfig {
+        let mut rule = rule(role, BindStrategy::Port, 0);
+        rule.port_path = Some(port(n));
+        rule
+    }
+
+    fn by_serial(role: &str, serial: &str) -> DeviceConfig {
+        let mut rule = rule(role, BindStrategy::Serial, 0);
+        rule.serial = Some(serial.to_string());
+        rule
+    }
+
+    fn by_topology(role: &str, chain: &str) -> DeviceConfig {
+        let mut rule = rule(role, BindStrategy::Topology, 0);
+        rule.topology = Some(TopologyAnchor {
+            root: AnchorRoot::Controller {
+                path: "0000:00:14.0".to_string(),
+                root_hub: Some(0),
+            },
+            chain: chain.to_string(),
+        });
+        rule
+    }
+
+    fn check(explained: &RuleExplanation, criterion: Criterion) -> &CriterionCheck {
+        explained
+            .checks
+            .iter()
+            .find(|c| c.criterion == criterion)
+            .unwrap()
+    }
+
+    #[test]
+    fn one_rule_matching_two_devices_is_ambiguous() {
+        // 两个没有序列号的 CH340，规则只按 VID/PID 匹配
+        let devices = vec![at(2, None), at(3, None)];
+        let rules = vec![rule("arm", BindStrategy::VidPid, 0), by_port("cam", 4)];
+
+        let conflicts = detect_conflicts(&devices, &rules);
+        assert_eq!(conflicts.len(), 1);
+        match &conflicts[0] {
+            Conflict::AmbiguousRole { role, devices: ids } => {
+                assert_eq!(role, "arm");
+                assert_eq!(ids, &vec![id(&devices[0]), id(&devices[1])]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // 两个设备都不绑定，角色也解析不出来
+        let views = match_raw_to_views(&devices, &rules);
+        assert!(
+            views
+                .iter()
+                .all(|v| v.role.is_none() && v.conflicts.len() == 1)
+        );
+        assert!(resolve_role(&devices, &rules, "arm").is_none());
+    }
+
+    #[test]
+    fn two_rules_matching_one_device_tie_without_affecting_others() {
+        // Serial 和 Topology 的具体程度相同，同时命中 2 号口的设备
+        let devices = vec![at(2, Some("SN1")), at(3, Some("SN2"))];
+        let rules = vec![
+            by_serial("left", "SN1"),
+            by_topology("right", "2"),
+            by_serial("other", "SN2"),
+        ];
+        assert!(validate_rules(&rules).is_ok());
+
+        let conflicts = detect_conflicts(&devices, &rules);
+        assert_eq!(conflicts.len(), 1);
+        assert!(matches!(
+            &conflicts[0],
+            Conflict::RuleTie { device, roles }
+                if device == &id(&devices[0]) && roles == &["left", "right"]
+        ));
+
+        let bindings = role_bindings(&devices, &rules);
+        assert_eq!(bindings.keys().collect::<Vec<_>>(), ["other"]);
+    }
+
+    #[test]
+    fn explain_reports_expected_and_actual_values() {
+        let devices = vec![at(2, Some("SN1"))];
+        let mut wrong_vid = by_port("wrong_vid", 2);
+        wrong_vid.vid = 0x0403;
+        let rules = vec![
+            wrong_vid,
+            by_serial("wrong_serial", "SN9"),
+            by_port("wrong_port", 3),
+            by_topology("wrong_topology", "3"),
+        ];
+
+        let explained = explain_device(&devices, &rules, &id(&devices[0])).unwrap();
+        assert!(explained.rules.iter().all(|r| !r.matched && !r.selected));
+
+        let vid = check(&explained.rules[0], Criterion::Vid);
+        assert_eq!(vid.expected.as_deref(), Some("0x0403"));
+        assert_eq!(vid.actual.as_deref(), Some("0x1a86"));
+        assert!(!vid.passed);
+        assert!(check(&explained.rules[0], Criterion::Pid).passed);
+
+        let serial = check(&explained.rules[1], Criterion::Serial);
+        assert_eq!(serial.expected.as_deref(), Some("SN9"));
+        assert_eq!(serial.actual.as_deref(), Some("SN1"));
+
+        let port_path = check(&explained.rules[2], Criterion::PortPath);
+        assert_eq!(port_path.expected, Some(port(3)));
+        assert_eq!(port_path.actual, Some(port(2)));
+
+        let topology = check(&explained.rules[3], Criterion::Topology);
+        assert_eq!(
+            topology.expected.as_deref(),
+            Some(rules[3].topology.as_ref().unwrap().to_string().as_str())
+        );
+        let actual = TopologyAnchor {
+            chain: "2".to_string(),
+            ..rules[3].topology.clone().unwrap()
+        };
+        assert_eq!(topology.actual, Some(actual.to_string()));
+        assert!(!topology.passed);
+    }
+
+    #[test]
+    fn explain_verdict_agrees_with_match_rule() {
+        let devices = vec![at(2, Some("SN1")), at(3, None), at(4, Some("SN4"))];
+        let rules = vec![
+            rule("fallback", BindStrategy::VidPid, 0),
+            by_port("cam", 2),
+            by_serial("arm", "SN4"),
+            by_topology("base", "3"),
+        ];
+
+        for raw in &devices {
+            let explained = explain_device(&devices, &rules, &id(raw)).unwrap();
+            for (rule, explanation) in rules.iter().zip(&explained.rules) {
+                assert_eq!(explanation.role, rule.role);
+                assert_eq!(
+                    explanation.matched,
+                    rule_matches(rule, raw, &devices),
+                    "{} on {}",
+                    rule.role,
+                    id(raw)
+                );
+            }
+
+            let selected: Vec<&str> = explained
+                .rules
+                .iter()
+                .filter(|r| r.selected)
+                .map(|r| r.role.as_str())
+                .collect();
+            let winner = match_rule(raw, &rules, &devices).map(|r| r.role.as_str());
+            assert_eq!(selected, winner.into_iter().collect::<Vec<_>>());
+            assert_eq!(explained.device.role.as_deref(), winner);
+        }
+    }
+
+    #[test]
+    fn preview_reports_orphaned_moved_roles_and_conflicts() {
+        let devices = vec![at(2, Some("SN1")), at(3, Some("SN2")), at(4, None)];
+        let current = vec![by_port("cam", 2), by_serial("arm", "SN2")];
+        // 候选规则删掉了 cam，arm 换到 SN1，新增的 spare 同时命中 3、4 号口
+        let candidate = vec![
+            by_serial("arm", "SN1"),
+            rule("spare", BindStrategy::VidPid, 0),
+        ];
+
+        let preview = preview_rules(&devices, &current, &candidate);
+        assert_eq!(preview.unbound, ["cam"]);
+        assert_eq!(preview.moved, ["arm"]);
+        assert!(preview.bound.is_empty());
+        assert!(preview.invalid.is_none());
+        assert!(matches!(
+            preview.conflicts.as_slice(),
+            [Conflict::AmbiguousRole { role, devices: ids }] if role == "spare" && ids.len() == 2
+        ));
+        assert_eq!(preview.devices[0].role.as_deref(), Some("arm"));
+        assert_eq!(preview.devices[1].role, None);
+    }
+
+    #[test]
+    fn preview_marks_invalid_candidates() {
+        let devices = vec![at(2, None)];
+        let current = vec![by_port("cam", 2)];
+        let candidate = vec![by_port("cam", 2), by_port("cam", 3)];
+
+        let preview = preview_rules(&devices, &current, &candidate);
+        assert!(preview.invalid.unwrap().contains("cam"));
+        assert!(preview.unbound.is_empty());
+
+        // 清空规则：所有已绑定的角色都会失去绑定
+        let preview = preview_rules(&devices, &current, &[]);
+        assert_eq!(preview.unbound, ["cam"]);
+        assert!(preview.invalid.is_none());
+    }
+
+    #[test]
+    fn role_statuses_report_online_offline_and_last_seen() {
+        let devices = vec![at(2, Some("SN1"))];
+        let mut cam = by_port("cam", 2);
+        cam.required = true;
+        let mut arm = by_serial("arm", "SN9");
+        arm.required = true;
+        let gripper = by_serial("gripper", "SN8");
+        let rules = vec![cam, arm, gripper];
+        let last_seen = HashMap::from([("arm".to_string(), 1_000), ("cam".to_string(), 2_000)]);
+
+        let statuses = role_statuses(&devices, &rules, &last_seen, 5_000);
+        let roles: Vec<&str> = statuses.iter().map(|s| s.role.as_str()).collect();
+        assert_eq!(roles, ["cam", "arm", "gripper"]);
+
+        // 在线角色的 last_seen 是当前时间
+        let cam = &statuses[0];
+        assert!(cam.online && cam.required);
+        assert_eq!(cam.last_seen_ms, Some(5_000));
+        assert_eq!(cam.system_path, Some(devices[0].system_path.clone()));
+        assert_eq!(
+            cam.device.as_ref().map(|d| d.id.clone()),
+            Some(id(&devices[0]))
+        );
+
+        // 离线的必需角色保留最后一次在线的时间
+        let arm = &statuses[1];
+        assert!(!arm.online && arm.required);
+        assert_eq!(arm.last_seen_ms, Some(1_000));
+        assert_eq!(arm.system_path, None);
+
+        // 从未出现过的角色
+        assert_eq!(statuses[2].last_seen_ms, None);
+
+        // 只有离线的必需角色会让 `doratool check` 失败
+        assert_eq!(missing_required(&statuses), ["arm"]);
+    }
+
+    #[test]
+    fn ambiguous_role_is_offline_with_its_conflict() {
+        let devices = vec![at(2, None), at(3, None)];
+        let mut arm = rule("arm", BindStrategy::VidPid, 0);
+        arm.required = true;
+
+        let statuses = role_statuses(&devices, &[arm], &HashMap::new(), 5_000);
+        assert!(!statuses[0].online);
+        assert!(matches!(
+            statuses[0].conflicts.as_slice(),
+            [Conflict::AmbiguousRole { .. }]
+        ));
+        assert_eq!(missing_required(&statuses), ["arm"]);
+        assert!(missing_required(&[]).is_empty());
+    }
+}
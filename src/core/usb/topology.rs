@@ -0,0 +1,230 @@
+//! Bus-number-independent USB topology, parsed from the sysfs path (`system_path` on Linux)
+//! `/sys/devices/pci0000:00/0000:00:14.0/usb1/1-2/1-2.1` -> controller `0000:00:14.0`, root hub 0, chain `2.1`
+// 与总线号无关的 USB 拓扑，从 sysfs 路径解析
+// 总线号 (usb1 / 1-2.1 里的 1) 在不同启动或内核版本之间可能变化，
+// 但控制器路径、root hub 在控制器下的序号和它下面的端口链是固定的
+
+use serde::Serialize;
+use usb_resolver::RawDeviceInfo;
+
+use crate::core::usb::models::{AnchorRoot, TopologyAnchor};
+
+/// 设备在 USB 树上的位置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Topology {
+    // 主控制器 (root hub 的父节点)，例如 PCI 地址 "0000:00:14.0" 或 "3f980000.usb"
+    pub controller: String,
+    // root hub 在控制器下的序号 (按总线号排序，从 0 开始)
+    // xHCI 控制器下同时有 USB2 和 USB3 两个 root hub，同一个物理口在两边的端口链相同
+    pub root_hub: usize,
+    // root hub 之下的端口链，例如 "2.1"；root hub 自身为空字符串
+    pub chain: String,
+}
+
+impl Topology {
+    /// 解析 sysfs 路径，非 Linux 平台或者不是 USB 设备节点时返回 None
+    /// root hub 的序号根据同一次扫描结果 `devices` 里的 root hub (usbN 节点) 计算，不读取 sysfs；
+    /// 列表里找不到设备所在的 root hub 时按 0 处理
+    pub fn parse(system_path: &str, devices: &[RawDeviceInfo]) -> Option<Self> {
+        Self::parse_with(system_path, |controller_dir| {
+            root_hub_buses(controller_dir, devices)
+        })
+    }
+
+    /// 同 `parse`，`root_buses` 根据控制器的 sysfs 目录返回它下面所有 root hub 的总线号
+    fn parse_with(system_path: &str, root_buses: impl FnOnce(&str) -> Vec<u32>) -> Option<Self> {
+        let components: Vec<&str> = system_path.split('/').filter(|c| !c.is_empty()).collect();
+
+        // 找到 root hub 节点 "usbN"
+        let root = components.iter().position(|c| root_hub_bus(c).is_some())?;
+        let controller = components.get(root.checked_sub(1)?)?.to_string();
+        let bus = &components[root][3..];
+
+        let last = components.last()?;
+        let chain = if root == components.len() - 1 {
+            String::new()
+        } else {
+            // "1-2.1" -> 总线号 "1" + 端口链 "2.1"
+            let (device_bus, chain) = last.split_once('-')?;
+            if device_bus != bus || chain.contains(':') {
+                // ":" 说明是 usb_interface 节点，不是设备
+                return None;
+            }
+            chain.to_string()
+        };
+
+        let controller_dir = format!("/{}", components[..root].join("/"));
+        let mut buses = root_buses(&controller_dir);
+        buses.sort_unstable();
+        let root_hub = root_hub_bus(components[root])
+            .and_then(|bus| buses.iter().position(|&b| b == bus))
+            .unwrap_or(0);
+
+        Some(Self {
+            controller,
+            root_hub,
+            chain,
+        })
+    }
+
+    /// 相对于某个上游 Hub 的端口链
+    /// 例如 Hub 在 "2"，设备在 "2.1.3"，返回 "1.3"；不在该 Hub 下面时返回 None
+    pub fn relative_to(&self, hub: &Topology) -> Option<String> {
+        if self.controller != hub.controller || self.root_hub != hub.root_hub {
+            return None;
+        }
+        if hub.chain.is_empty() {
+            return (!self.chain.is_empty()).then(|| self.chain.clone());
+        }
+        self.chain
+            .strip_prefix(hub.chain.as_str())
+            .and_then(|rest| rest.strip_prefix('.'))
+            .map(str::to_string)
+    }
+
+    /// 锚定在主控制器 (及 root hub) 上的拓扑锚点
+    pub fn anchor(&self) -> TopologyAnchor {
+        TopologyAnchor {
+            root: AnchorRoot::Controller {
+                path: self.controller.clone(),
+                root_hub: Some(self.root_hub),
+            },
+            chain: self.chain.clone(),
+        }
+    }
+}
+
+// "usb3" -> 3
+fn root_hub_bus(component: &str) -> Option<u32> {
+    component
+        .strip_prefix("usb")
+        .filter(|n| !n.is_empty() && n.chars().all(|ch| ch.is_ascii_digit()))
+        .and_then(|n| n.parse().ok())
+}
+
+// 扫描结果中位于控制器目录下的所有 root hub 的总线号
+fn root_hub_buses(controller_dir: &str, devices: &[RawDeviceInfo]) -> Vec<u32> {
+    devices
+        .iter()
+        .filter_map(|device| {
+            let (parent, name) = device.system_path.rsplit_once('/')?;
+            if parent != controller_dir {
+                return None;
+            }
+            root_hub_bus(name)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XHCI: &str = "/sys/devices/pci0000:00/0000:00:14.0";
+
+    // xHCI 控制器：usb1 是 USB2 root hub，usb2 是 USB3 root hub
+    fn parse(path: &str) -> Option<Topology> {
+        Topology::parse_with(path, |dir| {
+            assert_eq!(dir, XHCI);
+            vec![2, 1]
+        })
+    }
+
+    #[test]
+    fn parses_controller_root_hub_and_chain() {
+        let t = parse(&format!("{}/usb1/1-2/1-2.1", XHCI)).unwrap();
+        assert_eq!(t.controller, "0000:00:14.0");
+        assert_eq!(t.root_hub, 0);
+        assert_eq!(t.chain, "2.1");
+
+        let root = parse(&format!("{}/usb1", XHCI)).unwrap();
+        assert_eq!(root.chain, "");
+    }
+
+    #[test]
+    fn usb2_and_usb3_root_hubs_are_distinct() {
+        let high_speed = parse(&format!("{}/usb1/1-1", XHCI)).unwrap();
+        let super_speed = parse(&format!("{}/usb2/2-1", XHCI)).unwrap();
+
+        assert_eq!(high_speed.chain, super_speed.chain);
+        assert_eq!(high_speed.root_hub, 0);
+        assert_eq!(super_speed.root_hub, 1);
+        assert_ne!(high_speed, super_speed);
+        assert_ne!(high_speed.anchor(), super_speed.anchor());
+    }
+
+    #[test]
+    fn root_hub_ordinal_ignores_bus_numbers() {
+        // 重新枚举后总线号变了 (3、4)，序号不变
+        let t = Topology::parse_with(&format!("{}/usb4/4-1", XHCI), |_| vec![3, 4]).unwrap();
+        assert_eq!(t.root_hub, 1);
+
+        // 扫描结果里没有这个 root hub 时按 0 处理
+        let t = Topology::parse_with(&format!("{}/usb4/4-1", XHCI), |_| Vec::new()).unwrap();
+        assert_eq!(t.root_hub, 0);
+    }
+
+    #[test]
+    fn root_hub_ordinal_comes_from_scanned_root_hubs() {
+        let device = |system_path: String| RawDeviceInfo {
+            vid: 0x1d6b,
+            pid: 0x0002,
+            serial: None,
+            port_path: "N/A".to_string(),
+            system_path,
+            system_path_alt: None,
+        };
+        let devices = vec![
+            device(format!("{}/usb4", XHCI)),
+            device(format!("{}/usb3", XHCI)),
+            device(format!("{}/usb3/3-1", XHCI)),
+            // 其它控制器的 root hub 不参与排序
+            device("/sys/devices/pci0000:00/0000:00:1a.0/usb1".to_string()),
+        ];
+
+        let t = Topology::parse(&format!("{}/usb4/4-1", XHCI), &devices).unwrap();
+        assert_eq!(t.root_hub, 1);
+        let t = Topology::parse(&format!("{}/usb3/3-1", XHCI), &devices).unwrap();
+        assert_eq!(t.root_hub, 0);
+    }
+
+    #[test]
+    fn rejects_interfaces_and_non_usb_paths() {
+        assert_eq!(parse(&format!("{}/usb1/1-2/1-2:1.0", XHCI)), None);
+        assert_eq!(parse(&format!("{}/usb1/2-2", XHCI)), None);
+        assert_eq!(Topology::parse_with("/dev/ttyUSB0", |_| Vec::new()), None);
+        assert_eq!(
+            Topology::parse_with("IOService:/AppleUSB", |_| Vec::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn relative_to_hub_on_the_same_root_hub() {
+        let hub = parse(&format!("{}/usb1/1-2", XHCI)).unwrap();
+        let device = parse(&format!("{}/usb1/1-2/1-2.1/1-2.1.3", XHCI)).unwrap();
+        assert_eq!(device.relative_to(&hub).as_deref(), Some("1.3"));
+
+        let root = parse(&format!("{}/usb1", XHCI)).unwrap();
+        assert_eq!(device.relative_to(&root).as_deref(), Some("2.1.3"));
+        assert_eq!(root.relative_to(&root), None);
+
+        // "2" 不是 "21" 的上游
+        let other = parse(&format!("{}/usb1/1-21", XHCI)).unwrap();
+        assert_eq!(other.relative_to(&hub), None);
+    }
+
+    #[test]
+    fn relative_to_hub_on_the_other_root_hub_is_none() {
+        // USB3 Hub 会同时出现在两个 root hub 下 (同一个物理口)，设备只属于其中一边
+        let super_speed_hub = parse(&format!("{}/usb2/2-2", XHCI)).unwrap();
+        let high_speed_device = parse(&format!("{}/usb1/1-2/1-2.1", XHCI)).unwrap();
+        assert_eq!(high_speed_device.relative_to(&super_speed_hub), None);
+
+        let super_speed_device = parse(&format!("{}/usb2/2-2/2-2.1", XHCI)).unwrap();
+        assert_eq!(
+            super_speed_device.relative_to(&super_speed_hub).as_deref(),
+            Some("1")
+        );
+    }
+}
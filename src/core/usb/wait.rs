@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use tokio::{sync::broadcast::error::RecvError, time::Instant};
+
+use crate::{
+    core::usb::{models::ResolvedRole, service},
+    infra::state::AppState,
+};
+
+/// 等待所有角色都绑定到设备，由事件总线驱动 (每次设备/规则变化后重新检查)，不轮询
+/// 成功时按参数顺序返回解析结果，超时返回仍然缺失的角色
+pub async fn wait_for_roles(
+    state: &AppState,
+    roles: &[String],
+    timeout: Duration,
+) -> Result<Vec<ResolvedRole>, Vec<String>> {
+    // 先订阅再检查，避免检查之后、订阅之前的事件被漏掉
+    let mut events = state.subscribe();
+    // 超时时间过大 (加法溢出) 时视为不限时
+    let deadline = Instant::now().checked_add(timeout);
+
+    loop {
+        let missing = match resolve_all(state, roles) {
+            Ok(resolved) => return Ok(resolved),
+            Err(missing) => missing,
+        };
+
+        let received = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, events.recv()).await,
+            None => Ok(events.recv().await),
+        };
+        match received {
+            // 任何事件都可能改变绑定，重新检查即可；Lagged 同理
+            Ok(Ok(_)) | Ok(Err(RecvError::Lagged(_))) => {}
+            Ok(Err(RecvError::Closed)) | Err(_) => return Err(missing),
+        }
+    }
+}
+
+fn resolve_all(state: &AppState, roles: &[String]) -> Result<Vec<ResolvedRole>, Vec<String>> {
+    let rules = { state.rules.read().unwrap().clone() };
+    let raw_devices = { state.live_devices.read().unwrap().clone() };
+
+    let mut resolved = Vec::new();
+    let mut missing = Vec::new();
+    for role in roles {
+        match service::resolve_role(&raw_devices, &rules, role) {
+            Some(r) => resolved.push(r),
+            None => missing.push(role.clone()),
+        }
+    }
+
+    if missing.is_empty() {
+        Ok(resolved)
+    } else {
+        Err(missing)
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
 use directories::ProjectDirs;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use tracing::info;
 
@@ -9,9 +10,10 @@ use crate::core::usb::models::DeviceConfig;
 /// 应用路径管理
 /// 负责计算跨平台的标准路径 (Linux: ~/.local/share, ~/.config 等)
 pub struct AppPaths {
-    pub config_file: PathBuf, // ~/.config/dora-tool/usb_rules.json
-    pub log_dir: PathBuf,     // ~/.local/share/dora-tool/
-    pub pid_file: PathBuf,    // ~/.local/share/dora-tool/dora-tool.pid
+    pub config_file: PathBuf,   // ~/.config/dora-tool/usb_rules.json
+    pub settings_file: PathBuf, // ~/.config/dora-tool/settings.json
+    pub log_dir: PathBuf,       // ~/.local/share/dora-tool/
+    pub pid_file: PathBuf,      // ~/.local/share/dora-tool/dora-tool.pid
 }
 
 impl AppPaths {
@@ -38,6 +40,7 @@ impl AppPaths {
 
         Ok(Self {
             config_file: config_dir.join("usb_rules.json"),
+            settings_file: config_dir.join("settings.json"),
             log_dir: data_dir.to_path_buf(),
             pid_file: data_dir.join("dora-tool.pid"),
         })
@@ -66,3 +69,30 @@ pub fn load_rules(path: &Path) -> Result<Vec<DeviceConfig>> {
     info!("已加载 {} 条规则", rules.len());
     Ok(rules)
 }
+
+/// 保存规则配置
+/// 以格式化 (pretty) JSON 写入，方便手动查看和编辑
+/// 先写同目录下的临时文件再 rename，保证不会留下写了一半的配置文件 (见 `write_atomic`)
+pub fn save_rules(path: &Path, rules: &[DeviceConfig]) -> Result<()> {
+    let json_str = serde_json::to_string_pretty(rules).context("序列化规则失败")?;
+    write_atomic(path, json_str.as_bytes())?;
+
+    info!("已保存 {} 条规则", rules.len());
+    Ok(())
+}
+
+/// 原子写入文件：先写同目录下的 `<文件名>.tmp` 并 sync，再 rename 覆盖目标文件
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut tmp_name = path.file_name().context("无效的文件路径")?.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    {
+        let mut file = fs::File::create(&tmp_path)
+            .with_context(|| format!("无法创建临时文件: {:?}", tmp_path))?;
+        file.write_all(contents)
+            .with_context(|| format!("无法写入临时文件: {:?}", tmp_path))?;
+        file.sync_all()
+            .with_context(|| format!("无法同步临时文件: {:?}", tmp_path))?;
+    }
+    fs::rename(&tmp_path, path).with_context(|| format!("无法写入文件: {:?}", path))
+}
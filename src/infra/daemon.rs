@@ -0,0 +1,244 @@
+use std::{
+    fs::{self, File, TryLockError},
+    io::{self, PipeWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+use daemonize::{Daemonize, Outcome};
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+
+use crate::infra::config::AppPaths;
+
+// 前台进程等待守护进程启动完成的最长时间
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+// 启动失败时显示 daemon.err 的最后几行
+const ERROR_TAIL_LINES: usize = 20;
+
+/// 守护进程状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonStatus {
+    Running(i32),
+    Stopped,
+}
+
+/// 读取 pid 文件并检查守护进程是否还在运行
+/// 以 pid 文件上的锁 (flock) 为准：守护进程退出后锁自动释放，
+/// pid 文件残留或者 pid 被其它进程复用时都视为 Stopped
+pub fn status(pid_file: &Path) -> Result<DaemonStatus> {
+    if !is_locked(pid_file)? {
+        return Ok(DaemonStatus::Stopped);
+    }
+    match read_pid(pid_file)? {
+        Some(pid) => Ok(DaemonStatus::Running(pid)),
+        None => Ok(DaemonStatus::Stopped),
+    }
+}
+
+/// Daemon-side handle used to tell the foreground process that startup succeeded
+// 守护进程启动成功 (端口监听成功) 后通过它通知前台进程
+// 不调用 `notify` 就退出 (启动失败) 时管道关闭，前台进程会报告失败
+pub struct ReadySignal(PipeWriter);
+
+impl ReadySignal {
+    pub fn notify(mut self) {
+        let _ = self.0.write_all(b"1");
+    }
+}
+
+/// Fork into the background.
+/// Only the daemon (grandchild) process returns from this function. The calling process waits until the
+/// daemon reports it is ready (then exits 0) or fails (then returns the error, with the tail of daemon.err).
+/// Must be called BEFORE any tokio runtime or background thread is created.
+// 转入后台运行
+// 只有守护进程 (孙进程) 会从这个函数返回；调用方进程等待守护进程启动成功后直接退出，
+// 启动失败时返回错误 (附带 daemon.err 的最后几行)。
+// 必须在创建 tokio runtime 和任何后台线程之前调用 (fork 不会复制其它线程)
+pub fn daemonize(paths: &AppPaths, log_dir: &Path) -> Result<ReadySignal> {
+    if let DaemonStatus::Running(pid) = status(&paths.pid_file)? {
+        bail!("dora-tool is already running (pid {})", pid);
+    }
+
+    // 后台进程没有终端，把 stdout/stderr 重定向到日志目录，方便排查启动失败
+    // 追加写入，重启时不会丢掉上一次的输出
+    fs::create_dir_all(log_dir).with_context(|| format!("无法创建日志目录: {:?}", log_dir))?;
+    let err_path = log_dir.join("daemon.err");
+    let stdout = open_append(&log_dir.join("daemon.out"))?;
+    let stderr = open_append(&err_path)?;
+    let err_offset = stderr.metadata().map(|m| m.len()).unwrap_or(0);
+
+    println!(
+        "dora-tool is starting in background (pid file: {:?})",
+        paths.pid_file
+    );
+
+    let (reader, writer) = io::pipe().context("无法创建管道")?;
+
+    // pid 文件会被加锁 (flock)，第二个实例即使绕过上面的检查也无法启动
+    let outcome = Daemonize::new()
+        .pid_file(&paths.pid_file)
+        .working_directory(std::env::current_dir()?)
+        .stdout(stdout)
+        .stderr(stderr)
+        .execute();
+
+    match outcome {
+        Outcome::Child(child) => {
+            child.context("转入后台运行失败")?;
+            drop(reader);
+            Ok(ReadySignal(writer))
+        }
+        Outcome::Parent(parent) => {
+            parent.context("转入后台运行失败")?;
+            // 关闭自己的写端，守护进程退出后读端才能读到 EOF
+            drop(writer);
+            wait_ready(reader, &paths.pid_file, &err_path, err_offset)?;
+            std::process::exit(0)
+        }
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    File::options()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("无法打开 {:?}", path))
+}
+
+// 前台进程：等待守护进程的启动通知
+fn wait_ready(
+    mut reader: io::PipeReader,
+    pid_file: &Path,
+    err_path: &Path,
+    err_offset: u64,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0u8; 1];
+        let _ = tx.send(matches!(reader.read(&mut buf), Ok(1)));
+    });
+
+    match rx.recv_timeout(READY_TIMEOUT) {
+        Ok(true) => {
+            let pid = read_pid(pid_file)?.map_or("?".to_string(), |p| p.to_string());
+            println!("dora-tool started (pid {})", pid);
+            Ok(())
+        }
+        Ok(false) | Err(mpsc::RecvTimeoutError::Disconnected) => {
+            // 管道关闭时守护进程可能还没写完错误信息，等它真正退出 (pid 文件解锁) 再读日志
+            if wait_for_exit(pid_file, Duration::from_secs(2))? {
+                remove_pid_file(pid_file);
+            }
+            let output = read_tail(err_path, err_offset);
+            bail!(
+                "dora-tool failed to start{}",
+                if output.is_empty() {
+                    String::new()
+                } else {
+                    format!(":\n{}", output)
+                }
+            )
+        }
+        Err(mpsc::RecvTimeoutError::Timeout) => bail!(
+            "dora-tool did not report ready within {}s, see {:?}",
+            READY_TIMEOUT.as_secs(),
+            err_path
+        ),
+    }
+}
+
+// 本次启动写入 daemon.err 的最后几行
+fn read_tail(path: &Path, offset: u64) -> String {
+    let mut content = String::new();
+    if let Ok(mut file) = File::open(path)
+        && file.seek(SeekFrom::Start(offset)).is_ok()
+    {
+        let _ = file.read_to_string(&mut content);
+    }
+    let lines: Vec<&str> = content.trim_end().lines().collect();
+    lines[lines.len().saturating_sub(ERROR_TAIL_LINES)..].join("\n")
+}
+
+/// 停止正在运行的守护进程
+/// 先发送 SIGTERM，超过 timeout 仍未退出则升级为 SIGKILL
+pub fn stop(pid_file: &Path, timeout: Duration) -> Result<()> {
+    let pid = match status(pid_file)? {
+        DaemonStatus::Running(pid) => pid,
+        DaemonStatus::Stopped => {
+            remove_pid_file(pid_file);
+            bail!("dora-tool is not running");
+        }
+    };
+
+    println!("stopping dora-tool (pid {})...", pid);
+    kill(Pid::from_raw(pid), Signal::SIGTERM).context("发送 SIGTERM 失败")?;
+
+    if !wait_for_exit(pid_file, timeout)? {
+        println!(
+            "dora-tool did not exit within {}s, sending SIGKILL",
+            timeout.as_secs()
+        );
+        kill(Pid::from_raw(pid), Signal::SIGKILL).context("发送 SIGKILL 失败")?;
+
+        if !wait_for_exit(pid_file, Duration::from_secs(2))? {
+            bail!("dora-tool (pid {}) is still alive after SIGKILL", pid);
+        }
+    }
+
+    remove_pid_file(pid_file);
+    println!("dora-tool stopped");
+    Ok(())
+}
+
+fn read_pid(pid_file: &Path) -> Result<Option<i32>> {
+    if !pid_file.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(pid_file)
+        .with_context(|| format!("无法读取 pid 文件: {:?}", pid_file))?;
+
+    // 文件为空说明守护进程还没来得及写入 pid
+    Ok(content.trim().parse().ok())
+}
+
+// 守护进程启动后一直持有 pid 文件的排它锁 (见 `daemonize`)
+// 这里尝试加锁：加锁失败说明守护进程还在运行，加锁成功则立即释放 (drop 时关闭文件)
+fn is_locked(pid_file: &Path) -> Result<bool> {
+    let file = match File::open(pid_file) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e).with_context(|| format!("无法打开 pid 文件: {:?}", pid_file)),
+    };
+    match file.try_lock() {
+        Ok(()) => Ok(false),
+        Err(TryLockError::WouldBlock) => Ok(true),
+        Err(TryLockError::Error(e)) => {
+            Err(e).with_context(|| format!("无法检查 pid 文件锁: {:?}", pid_file))
+        }
+    }
+}
+
+fn wait_for_exit(pid_file: &Path, timeout: Duration) -> Result<bool> {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if !is_locked(pid_file)? {
+            return Ok(true);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    Ok(!is_locked(pid_file)?)
+}
+
+fn remove_pid_file(pid_file: &Path) {
+    if pid_file.exists() {
+        let _ = fs::remove_file(pid_file);
+    }
+}
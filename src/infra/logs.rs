@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use time::UtcOffset;
 use time::macros::format_description;
 use tracing_appender::non_blocking::WorkerGuard;
@@ -11,7 +13,7 @@ use tracing_subscriber::{
 /// 初始化日志系统
 /// 返回一个 WorkerGuard，**必须**在 main 函数中持有它直到程序结束，
 /// 否则日志可能无法写入文件。
-pub fn init(log_dir: &str) -> WorkerGuard {
+pub fn init(log_dir: &Path, log_level: &str) -> WorkerGuard {
     // 1. 设置文件滚动策略 (按天滚动)
     // 文件名格式: dora-tool.2023-10-27.log
     let file_appender = tracing_appender::rolling::daily(log_dir, "dora-tool.log");
@@ -44,8 +46,8 @@ pub fn init(log_dir: &str) -> WorkerGuard {
         .with_writer(non_blocking);
 
     // 6. 注册全局订阅者
-    // RUST_LOG=info 环境变量可以控制级别，默认 info
-    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    // 级别由 Settings::log_level 控制 (可被 DORATOOL_LOG_LEVEL / RUST_LOG / --log-level 覆盖)，默认 info
+    let env_filter = EnvFilter::try_new(log_level).unwrap_or_else(|_| EnvFilter::new("info"));
 
     tracing_subscriber::registry()
         .with(env_filter)
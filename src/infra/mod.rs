@@ -1,4 +1,6 @@
 pub mod config;
 pub mod daemon;
 pub mod logs;
+pub mod settings;
 pub mod state;
+pub mod watcher;
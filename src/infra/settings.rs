@@ -0,0 +1,240 @@
+use std::{
+    env, fs,
+    net::{SocketAddr, ToSocketAddrs},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::infra::config::AppPaths;
+
+// 轮询间隔的下限，过小 (例如 0) 会让扫描线程占满一个 CPU 核
+const MIN_SCAN_INTERVAL_MS: u64 = 50;
+// 慢扫描阈值的下限，过小会把每次正常扫描都报告为 I/O 阻塞
+const MIN_SLOW_SCAN_THRESHOLD_MS: u64 = 100;
+
+/// Server settings
+/// Priority (low -> high): built-in defaults < settings.json < DORATOOL_* env vars < CLI flags
+// 服务配置
+// 优先级 (低 -> 高): 内置默认值 < settings.json < DORATOOL_* 环境变量 < 命令行参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Listener address
+    // 监听地址
+    pub bind: String,
+    pub port: u16,
+    // Sleep between two polling scans (ms)
+    // 两次轮询扫描之间的间隔 (毫秒)
+    pub scan_interval_ms: u64,
+    // A scan slower than this is reported as I/O blocking (ms)
+    // 扫描耗时超过该值时视为 I/O 阻塞 (毫秒)
+    pub slow_scan_threshold_ms: u64,
+    // None means AppPaths::log_dir
+    // 为空时使用 AppPaths::log_dir
+    pub log_dir: Option<PathBuf>,
+    // tracing filter, e.g. "info" or "doratool=debug"
+    // tracing 过滤规则，例如 "info" 或 "doratool=debug"
+    pub log_level: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            bind: "0.0.0.0".to_string(),
+            port: 3000,
+            scan_interval_ms: 300,
+            slow_scan_threshold_ms: 1000,
+            log_dir: None,
+            log_level: "info".to_string(),
+        }
+    }
+}
+
+/// Overrides coming from the command line, `None` means "not given"
+// 来自命令行的覆盖项，None 表示未指定
+#[derive(Debug, Clone, Default)]
+pub struct SettingsOverrides {
+    pub bind: Option<String>,
+    pub port: Option<u16>,
+    pub scan_interval_ms: Option<u64>,
+    pub slow_scan_threshold_ms: Option<u64>,
+    pub log_dir: Option<PathBuf>,
+    pub log_level: Option<String>,
+}
+
+impl Settings {
+    /// 按层级加载配置: 文件 -> 环境变量 -> 命令行
+    pub fn load(settings_file: &Path, overrides: SettingsOverrides) -> Result<Self> {
+        let mut settings = Self::from_file(settings_file)?;
+        settings.apply_env()?;
+        settings.apply_overrides(overrides);
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// 合并后的取值检查 (不管来自文件、环境变量还是命令行)
+    fn validate(&self) -> Result<()> {
+        if self.scan_interval_ms < MIN_SCAN_INTERVAL_MS {
+            bail!(
+                "scan_interval_ms 不能小于 {} (当前为 {})",
+                MIN_SCAN_INTERVAL_MS,
+                self.scan_interval_ms
+            );
+        }
+        if self.slow_scan_threshold_ms < MIN_SLOW_SCAN_THRESHOLD_MS {
+            bail!(
+                "slow_scan_threshold_ms 不能小于 {} (当前为 {})",
+                MIN_SLOW_SCAN_THRESHOLD_MS,
+                self.slow_scan_threshold_ms
+            );
+        }
+        Ok(())
+    }
+
+    /// 读取 settings.json
+    /// 文件不存在或为空时使用默认值，缺失的字段也使用默认值
+    fn from_file(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content =
+            fs::read_to_string(path).with_context(|| format!("无法读取配置文件: {:?}", path))?;
+        if content.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        serde_json::from_str(&content).with_context(|| format!("解析配置文件失败: {:?}", path))
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        if let Some(v) = env_var::<String>("DORATOOL_BIND")? {
+            self.bind = v;
+        }
+        if let Some(v) = env_var("DORATOOL_PORT")? {
+            self.port = v;
+        }
+        if let Some(v) = env_var("DORATOOL_SCAN_INTERVAL_MS")? {
+            self.scan_interval_ms = v;
+        }
+        if let Some(v) = env_var("DORATOOL_SLOW_SCAN_THRESHOLD_MS")? {
+            self.slow_scan_threshold_ms = v;
+        }
+        if let Some(v) = env_var::<PathBuf>("DORATOOL_LOG_DIR")? {
+            self.log_dir = Some(v);
+        }
+        // 兼容以前的 RUST_LOG，DORATOOL_LOG_LEVEL 优先
+        if let Some(v) = env_var::<String>("RUST_LOG")? {
+            self.log_level = v;
+        }
+        if let Some(v) = env_var::<String>("DORATOOL_LOG_LEVEL")? {
+            self.log_level = v;
+        }
+        Ok(())
+    }
+
+    fn apply_overrides(&mut self, overrides: SettingsOverrides) {
+        if let Some(v) = overrides.bind {
+            self.bind = v;
+        }
+        if let Some(v) = overrides.port {
+            self.port = v;
+        }
+        if let Some(v) = overrides.scan_interval_ms {
+            self.scan_interval_ms = v;
+        }
+        if let Some(v) = overrides.slow_scan_threshold_ms {
+            self.slow_scan_threshold_ms = v;
+        }
+        if let Some(v) = overrides.log_dir {
+            self.log_dir = Some(v);
+        }
+        if let Some(v) = overrides.log_level {
+            self.log_level = v;
+        }
+    }
+
+    /// 监听地址，例如 0.0.0.0:3000
+    /// `bind` 可以是 IP (IPv6 可带方括号) 或主机名 (如 localhost)，主机名解析出多个地址时取第一个
+    pub fn listen_addr(&self) -> Result<SocketAddr> {
+        let host = self
+            .bind
+            .strip_prefix('[')
+            .and_then(|h| h.strip_suffix(']'))
+            .unwrap_or(&self.bind);
+        (host, self.port)
+            .to_socket_addrs()
+            .with_context(|| format!("无效的监听地址: {}:{}", self.bind, self.port))?
+            .next()
+            .with_context(|| format!("无法解析监听地址: {}:{}", self.bind, self.port))
+    }
+
+    pub fn scan_interval(&self) -> Duration {
+        Duration::from_millis(self.scan_interval_ms)
+    }
+
+    pub fn slow_scan_threshold(&self) -> Duration {
+        Duration::from_millis(self.slow_scan_threshold_ms)
+    }
+
+    /// 日志目录，未配置时使用标准数据目录
+    pub fn log_dir(&self, paths: &AppPaths) -> PathBuf {
+        self.log_dir
+            .clone()
+            .unwrap_or_else(|| paths.log_dir.clone())
+    }
+}
+
+/// 读取并解析环境变量，未设置或为空时返回 None
+fn env_var<T>(key: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env::var(key) {
+        Ok(v) if !v.trim().is_empty() => v
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("环境变量 {} 的值无效 '{}': {}", key, v, e)),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(Settings::default().validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_busy_spinning_scan_interval() {
+        let mut settings = Settings::default();
+        settings.apply_overrides(SettingsOverrides {
+            scan_interval_ms: Some(0),
+            ..Default::default()
+        });
+        let err = settings.validate().unwrap_err().to_string();
+        assert!(err.contains("scan_interval_ms"), "{}", err);
+
+        settings.scan_interval_ms = MIN_SCAN_INTERVAL_MS;
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_tiny_slow_scan_threshold() {
+        let settings = Settings {
+            slow_scan_threshold_ms: 0,
+            ..Default::default()
+        };
+        let err = settings.validate().unwrap_err().to_string();
+        assert!(err.contains("slow_scan_threshold_ms"), "{}", err);
+    }
+}
@@ -1,11 +1,23 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     path::PathBuf,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
 };
 
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use tokio::sync::broadcast;
 use usb_resolver::RawDeviceInfo;
 
-use crate::core::usb::models::DeviceConfig;
+use crate::core::usb::{
+    diff::DeviceChange,
+    events::{self, AppEvent, EventBus, EventEnvelope},
+    models::{DeviceConfig, DeviceView},
+    service,
+};
+
+/// 事件总线容量：订阅者落后超过这么多条事件时会收到 Lagged，
+/// 同时也是可用于断线续传 (Last-Event-ID) 的历史事件条数
+const EVENT_BUS_CAPACITY: usize = 256;
 
 #[derive(Debug, Clone)]
 pub struct AppState {
@@ -15,21 +27,217 @@ pub struct AppState {
     // Persistent rules (Web server modifies, USB worker reads)
     // 持久化规则 (Web server修改, USB worker读取)
     pub rules: Arc<RwLock<Vec<DeviceConfig>>>,
+    // Serializes read-modify-write updates of the rules (see `core::usb::rules`)
+    // 串行化规则的"读-改-写"操作 (见 `core::usb::rules`)
+    pub rules_update: Arc<Mutex<()>>,
     // Real-time device list (modified by USB worker, read by Web server)
     // Here we store RawDeviceInfo; the Web layer is responsible for rendering it into a view.
     // 实时的设备列表 (USB worker修改, Web server读取)
     // 这里我们存 RawDeviceInfo，Web层负责渲染成 View
     pub live_devices: Arc<RwLock<Vec<RawDeviceInfo>>>,
+    // Rules-changed notification (sender side, see `set_rules`)
+    // The USB event thread listens on `rules_changed_rx` and refreshes its rules snapshot.
+    // 规则变更通知 (发送端，见 `set_rules`)
+    // USB 事件线程监听 `rules_changed_rx`，收到后刷新自己的规则快照
+    pub rules_changed_tx: Sender<()>,
+    pub rules_changed_rx: Receiver<()>,
+    // In-process event bus (device attach/detach, role bound/unbound, rules changed, scan errors)
+    // Publishers: USB polling/event threads and `set_rules`. Subscribers: web handlers, hooks, exporters.
+    // 进程内事件总线 (设备插拔、角色绑定/解绑、规则变更、扫描错误)
+    // 发布者：USB 轮询/事件线程和 `set_rules`；订阅者：Web handler、钩子、导出器等
+    pub events: EventBus,
+    // Asks the polling thread to scan immediately instead of waiting for the next tick
+    // 请求轮询线程立即扫描，而不是等待下一个周期
+    pub rescan_tx: Sender<()>,
+    pub rescan_rx: Receiver<()>,
+    // Last time (unix ms) each role was seen bound to a device, kept in memory only
+    // 每个角色最后一次绑定到设备的时间 (Unix 毫秒)，只保存在内存中
+    pub role_last_seen: Arc<RwLock<HashMap<String, u64>>>,
 }
 
 impl AppState {
     // new state
     // 创建新的状态
     pub fn new(config_path: PathBuf, rules: Vec<DeviceConfig>) -> Self {
+        let (rules_changed_tx, rules_changed_rx) = unbounded();
+        let events = EventBus::new(EVENT_BUS_CAPACITY);
+        let (rescan_tx, rescan_rx) = unbounded();
         Self {
             config_path,
             rules: Arc::new(RwLock::new(rules)),
+            rules_update: Arc::new(Mutex::new(())),
             live_devices: Arc::new(RwLock::new(Vec::new())),
+            rules_changed_tx,
+            rules_changed_rx,
+            events,
+            rescan_tx,
+            rescan_rx,
+            role_last_seen: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    // Trigger an immediate USB scan
+    // 触发一次立即扫描
+    pub fn request_rescan(&self) {
+        let _ = self.rescan_tx.send(());
+    }
+
+    // Subscribe to the event bus
+    // 订阅事件总线
+    pub fn subscribe(&self) -> broadcast::Receiver<EventEnvelope> {
+        self.events.subscribe()
+    }
+
+    // Publish an event; having no subscriber is not an error
+    // 发布事件；没有订阅者时直接忽略
+    pub fn publish(&self, event: AppEvent) {
+        self.events.publish(event);
+    }
+
+    // Apply a modification to `live_devices` and publish the resulting events
+    // `update` returns the changes it made (see `diff::apply_changes`).
+    // 修改 `live_devices` 并发布对应的事件
+    // `update` 需要返回它做出的变更 (见 `diff::apply_changes`)
+    pub fn update_devices<F>(&self, update: F)
+    where
+        F: FnOnce(&mut Vec<RawDeviceInfo>) -> Vec<DeviceChange>,
+    {
+        // 先拿规则快照再拿设备写锁，不同时持有两把锁，避免和 set_rules 死锁
+        let rules = self.rules.read().unwrap().clone();
+
+        let (changes, before, after) = {
+            let mut devices = self.live_devices.write().unwrap();
+            let before = service::match_raw_to_views(&devices, &rules);
+            let changes = update(&mut devices);
+            let after = service::match_raw_to_views(&devices, &rules);
+            (changes, before, after)
+        };
+
+        for event in events::device_events(&changes, &before, &after) {
+            self.publish(event);
+        }
+        let (before, after) = (service::bindings_of(&before), service::bindings_of(&after));
+        self.touch_roles(&before, &after);
+        for event in events::role_events(&before, &after) {
+            self.publish(event);
+        }
+    }
+
+    // Replace the in-memory rules and notify the USB event thread
+    // All rule updates should go through here instead of writing `rules` directly.
+    // 替换内存中的规则，并通知 USB 事件线程
+    // 所有规则更新都应该走这里，而不是直接写 `rules`
+    pub fn set_rules(&self, rules: Vec<DeviceConfig>) {
+        let devices = self.live_devices.read().unwrap().clone();
+        let after = service::role_bindings(&devices, &rules);
+        let count = rules.len();
+
+        let old_rules = {
+            let mut w = self.rules.write().unwrap();
+            std::mem::replace(&mut *w, rules)
+        };
+        let before = service::role_bindings(&devices, &old_rules);
+
+        // unbounded channel，不会阻塞；事件线程未启动时忽略即可
+        let _ = self.rules_changed_tx.send(());
+
+        self.touch_roles(&before, &after);
+        self.publish(AppEvent::RulesChanged { count });
+        for event in events::role_events(&before, &after) {
+            self.publish(event);
         }
     }
+
+    // Record the last-seen time of roles bound before or after a change
+    // A role that just went offline keeps the time it disappeared.
+    // 记录变更前后处于绑定状态的角色的最后在线时间
+    // 刚下线的角色保留的就是它消失的时间
+    fn touch_roles(
+        &self,
+        before: &BTreeMap<String, DeviceView>,
+        after: &BTreeMap<String, DeviceView>,
+    ) {
+        let now = events::now_ms();
+        let mut last_seen = self.role_last_seen.write().unwrap();
+        for role in before.keys().chain(after.keys()) {
+            last_seen.insert(role.clone(), now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::usb::models::BindStrategy;
+
+    fn device() -> RawDeviceInfo {
+        RawDeviceInfo {
+            vid: 0x1a86,
+            pid: 0x7523,
+            serial: Some("SN1".to_string()),
+            port_path: "pci-0000:00:14.0-usb-0:2:1.0".to_string(),
+            system_path: "/sys/devices/pci0000:00/0000:00:14.0/usb1/1-2".to_string(),
+            system_path_alt: Some("/dev/ttyUSB0".to_string()),
+        }
+    }
+
+    fn state() -> AppState {
+        let rule = DeviceConfig {
+            role: "arm".to_string(),
+            vid: 0x1a86,
+            pid: 0x7523,
+            strategy: BindStrategy::Serial,
+            serial: Some("SN1".to_string()),
+            port_path: None,
+            topology: None,
+            priority: 0,
+            required: true,
+        };
+        AppState::new(PathBuf::new(), vec![rule])
+    }
+
+    fn last_seen(state: &AppState) -> Option<u64> {
+        state.role_last_seen.read().unwrap().get("arm").copied()
+    }
+
+    #[test]
+    fn role_last_seen_is_recorded_on_attach_and_kept_after_detach() {
+        let state = state();
+        assert_eq!(last_seen(&state), None);
+
+        let raw = device();
+        state.update_devices(|devices| {
+            devices.push(raw.clone());
+            vec![DeviceChange::Added(raw.clone())]
+        });
+        let attached = last_seen(&state).expect("recorded on attach");
+
+        state.update_devices(|devices| {
+            devices.clear();
+            vec![DeviceChange::Removed(raw.clone())]
+        });
+        let detached = last_seen(&state).expect("kept after detach");
+        assert!(detached >= attached);
+
+        // 离线后的状态使用记录下来的时间
+        let rules = state.rules.read().unwrap().clone();
+        let seen = state.role_last_seen.read().unwrap().clone();
+        let statuses = service::role_statuses(&[], &rules, &seen, detached + 60_000);
+        assert!(!statuses[0].online);
+        assert_eq!(statuses[0].last_seen_ms, Some(detached));
+        assert_eq!(service::missing_required(&statuses), ["arm"]);
+    }
+
+    #[test]
+    fn unrelated_device_does_not_touch_last_seen() {
+        let state = state();
+        let mut other = device();
+        other.serial = Some("OTHER".to_string());
+
+        state.update_devices(|devices| {
+            devices.push(other.clone());
+            vec![DeviceChange::Added(other.clone())]
+        });
+        assert_eq!(last_seen(&state), None);
+    }
 }
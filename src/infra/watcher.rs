@@ -0,0 +1,119 @@
+use std::{fs, path::Path, sync::mpsc, thread, time::Duration};
+
+use anyhow::{Context, Result, bail};
+use notify::{EventKind, RecursiveMode, Watcher};
+use tracing::{error, info, warn};
+
+use crate::{
+    core::usb::{models::DeviceConfig, service},
+    infra::state::AppState,
+};
+
+/// 多次连续写入 (例如 Ansible 先写临时文件再 rename) 合并为一次重载
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Start watching `AppState::config_path` for external edits, plus SIGHUP as an explicit trigger.
+/// Must be called inside the tokio runtime (the SIGHUP listener is a tokio task).
+// 监听 usb_rules.json 的外部修改，以及 SIGHUP 信号 (手动触发重载)
+// 必须在 tokio runtime 内调用 (SIGHUP 监听是一个 tokio 任务)
+pub fn start_rules_watcher(state: AppState) {
+    let state_for_file = state.clone();
+    thread::spawn(move || {
+        if let Err(e) = run_file_watcher(&state_for_file) {
+            error!("无法监听配置文件 {:?}: {:#}", state_for_file.config_path, e);
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(sig) => sig,
+            Err(e) => {
+                error!("无法监听 SIGHUP: {}", e);
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            info!("📨 收到 SIGHUP，重新加载规则");
+            reload_rules(&state);
+        }
+    });
+}
+
+fn run_file_watcher(state: &AppState) -> Result<()> {
+    // 监听所在目录而不是文件本身：
+    // 很多工具 (Ansible / vim) 会先写临时文件再 rename，直接监听文件会丢失后续事件
+    let dir = state
+        .config_path
+        .parent()
+        .context("配置文件路径没有父目录")?;
+    let file_name = state.config_path.file_name().context("配置文件路径无效")?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    info!(
+        "👀 [Thread-Watch] 正在监听配置文件: {:?}",
+        state.config_path
+    );
+
+    // 删除事件不触发重载：编辑器"删除再 rename"保存时文件会短暂消失，
+    // 之后的 Create/Modify 会再触发一次；真的被删掉时继续使用旧规则
+    let is_config_event = |event: &notify::Event| {
+        matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+            && event.paths.iter().any(|p| p.file_name() == Some(file_name))
+    };
+
+    loop {
+        match rx.recv() {
+            Ok(Ok(event)) if is_config_event(&event) => {
+                // 等待写入完成，吞掉这段时间内的后续事件
+                while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+                info!("📝 检测到配置文件变更，重新加载规则");
+                reload_rules(state);
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => warn!("配置文件监听出错: {}", e),
+            Err(_) => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// 重新解析并校验配置文件，成功后原子替换 `AppState::rules`
+/// 文件不存在、为空 (例如写了一半)、解析或校验失败时都保留旧规则
+pub fn reload_rules(state: &AppState) {
+    match load_and_validate(&state.config_path) {
+        Ok(new_rules) => {
+            if *state.rules.read().unwrap() == new_rules {
+                // 例如 Web 端刚刚保存过，内容没有变化
+                return;
+            }
+            info!("✅ 规则已重新加载: {} 条", new_rules.len());
+            state.set_rules(new_rules);
+        }
+        Err(e) => error!(
+            "❌ 重新加载规则失败，继续使用旧规则 ({:?}): {:#}",
+            state.config_path, e
+        ),
+    }
+}
+
+fn load_and_validate(path: &Path) -> Result<Vec<DeviceConfig>> {
+    // `config::load_rules` 把缺失或空文件当作"没有规则"，那是首次启动时的行为；
+    // 运行中重载时这通常只是保存到一半，不能因此清空所有规则 (清空请写入 `[]`)
+    let content =
+        fs::read_to_string(path).with_context(|| format!("无法读取配置文件: {:?}", path))?;
+    if content.trim().is_empty() {
+        bail!("配置文件为空: {:?}", path);
+    }
+
+    let rules: Vec<DeviceConfig> =
+        serde_json::from_str(&content).context("解析 JSON 配置文件失败，请检查格式")?;
+    service::validate_rules(&rules)?;
+    Ok(rules)
+}
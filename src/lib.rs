@@ -1,40 +1,77 @@
 use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use tokio::net::TcpListener;
 use tracing::info;
 
-use crate::{core::usb, infra::state::AppState};
+use crate::{
+    core::usb,
+    infra::{config::AppPaths, settings::Settings, state::AppState},
+};
 
 pub mod cli;
+pub mod client;
 pub mod core;
 pub mod infra;
 pub mod server;
 
-pub async fn run() -> Result<()> {
-    // 1. 初始化日志系统 (保存到当前目录下的 logs 文件夹)
+/// 启动服务，阻塞直到收到退出信号
+/// `on_ready` 在端口监听成功后调用 (守护进程据此通知前台进程启动成功)
+pub async fn run(paths: AppPaths, settings: Settings, on_ready: impl FnOnce()) -> Result<()> {
+    // 1. 初始化日志系统 (默认保存到 AppPaths::log_dir，可通过 Settings 修改)
     // _guard 必须存在于 main 的整个生命周期
-    let _guard = infra::logs::init("./logs");
+    let log_dir = settings.log_dir(&paths);
+    let _guard = infra::logs::init(&log_dir, &settings.log_level);
 
     info!("系统启动中...");
-
-    // init logs
-    // tracing_subscriber::fmt::init();
+    info!("Config File: {:?}", paths.config_file);
+    info!("Log Dir:     {:?}", log_dir);
 
     // init infra
-    let paths = infra::config::AppPaths::new()?;
+    let addr = settings.listen_addr()?;
     let rules = infra::config::load_rules(&paths.config_file)?;
+    // 与 CRUD 接口和热重载使用同一套校验，否则启动时能加载的文件之后每次修改都会失败
+    usb::service::validate_rules(&rules)
+        .with_context(|| format!("规则配置无效: {:?}", paths.config_file))?;
 
     // app state
     let state = Arc::new(AppState::new(paths.config_file, rules));
 
-    usb::manager::start_background_monitor(state.as_ref().clone());
+    usb::manager::start_background_monitor(state.as_ref().clone(), &settings);
+    infra::watcher::start_rules_watcher(state.as_ref().clone());
 
-    info!("Web Server listening on port 3000...");
+    info!("Web Server listening on {}...", addr);
     let app = server::routes::create_router(state.clone());
-    let listener = TcpListener::bind("0.0.0.0:3000").await?;
+    let listener = TcpListener::bind(addr).await?;
+    on_ready();
 
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
 
+    info!("系统已退出");
     Ok(())
 }
+
+/// 等待退出信号 (Ctrl+C 或 `doratool daemon stop` 发送的 SIGTERM)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("收到退出信号，正在关闭服务...");
+}
@@ -1,6 +1,6 @@
-#[tokio::main]
-async fn main() {
-    if let Err(err) = doratool::run().await {
-        println!("Server Err: {}", err);
+fn main() {
+    if let Err(err) = doratool::cli::run() {
+        eprintln!("Error: {:#}", err);
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,66 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::stream::{self, Stream, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::{core::usb::events::EventEnvelope, infra::state::AppState};
+
+/// `GET /api/events`
+/// Server-Sent Events 推送设备和角色变化
+/// 每条事件带单调递增的 `id`，断线重连时浏览器会自动带上 `Last-Event-ID`，服务端补发错过的事件。
+/// 如果错过的事件已经不在历史缓冲区里 (或者订阅者落后太多)，会先发送一条 `resync` 事件，
+/// 客户端收到后应该重新拉取 `/api/devices` 全量数据。
+pub async fn event_stream(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+
+    let replay = state.events.subscribe_since(last_event_id);
+
+    // 1. 历史断档提示
+    let resync = (!replay.complete).then(resync_event);
+
+    // 2. 补发历史事件
+    let backlog = replay.events.into_iter().map(|e| to_sse_event(&e));
+
+    // 3. 实时事件
+    let live = stream::unfold(replay.receiver, |mut rx| async move {
+        match rx.recv().await {
+            Ok(envelope) => Some((to_sse_event(&envelope), rx)),
+            Err(RecvError::Lagged(n)) => {
+                warn!("SSE 订阅者落后 {} 条事件，通知客户端重新同步", n);
+                Some((resync_event(), rx))
+            }
+            Err(RecvError::Closed) => None,
+        }
+    });
+
+    let stream = stream::iter(resync.into_iter().chain(backlog))
+        .chain(live)
+        .map(Ok);
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn to_sse_event(envelope: &EventEnvelope) -> Event {
+    Event::default()
+        .id(envelope.id.to_string())
+        .json_data(envelope)
+        .unwrap_or_else(|e| Event::default().comment(format!("serialize error: {}", e)))
+}
+
+fn resync_event() -> Event {
+    Event::default()
+        .event("resync")
+        .data("missed events, please reload")
+}
@@ -0,0 +1,47 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{Json, extract::State};
+use serde::Deserialize;
+
+use crate::{
+    core::usb::{self, learn::LearnOutcome, models::BindStrategy},
+    infra::state::AppState,
+    server::{
+        error::ApiError,
+        response::{ApiResponse, ApiResult},
+    },
+};
+
+/// `POST /api/learn` 的请求体
+#[derive(Debug, Deserialize)]
+pub struct LearnRequest {
+    pub role: String,
+    // 不填时由服务端选择默认策略 (优先端口)
+    #[serde(default)]
+    pub strategy: Option<BindStrategy>,
+    // 最长等待时间 (秒)
+    #[serde(default = "default_learn_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_learn_timeout() -> u64 {
+    60
+}
+
+/// `POST /api/learn`
+/// 学习模式：等待下一个插入的设备并绑定到角色；期间拔出已绑定的设备会返回它的角色
+pub async fn learn(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<LearnRequest>,
+) -> ApiResult<LearnOutcome> {
+    let timeout = Duration::from_secs(req.timeout_secs);
+    let outcome = usb::learn::learn(&state, &req.role, req.strategy, timeout)
+        .await?
+        .ok_or_else(|| {
+            ApiError::Timeout.with_msg(format!(
+                "no device was plugged in within {}s",
+                req.timeout_secs
+            ))
+        })?;
+    Ok(ApiResponse::success(outcome))
+}
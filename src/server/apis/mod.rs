@@ -1,2 +1,7 @@
+pub mod events;
+pub mod learn;
+pub mod roles;
+pub mod rules;
 pub mod usb;
 pub mod web;
+pub mod ws;
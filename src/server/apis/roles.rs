@@ -0,0 +1,145 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::{
+    core::usb::{
+        self,
+        env::EnvFormat,
+        events::now_ms,
+        models::{DeviceConfig, ResolvedRole, RoleStatus},
+    },
+    infra::state::AppState,
+    server::{
+        error::ApiError,
+        response::{ApiResponse, ApiResult},
+    },
+};
+
+/// `GET /api/roles`
+/// 以角色为中心的视图：每条规则在线/离线、最后在线时间和当前系统路径
+pub async fn list_roles(State(state): State<Arc<AppState>>) -> ApiResult<Vec<RoleStatus>> {
+    let rules = { state.rules.read().unwrap().clone() };
+    let raw_devices = { state.live_devices.read().unwrap().clone() };
+    let last_seen = { state.role_last_seen.read().unwrap().clone() };
+
+    let roles = usb::service::role_statuses(&raw_devices, &rules, &last_seen, now_ms());
+
+    Ok(ApiResponse::success(roles))
+}
+
+/// `GET /api/roles/{role}/resolve`
+/// 角色当前对应的设备路径 (优先 /dev 节点)；未配置、离线或存在冲突时返回 NotFound
+pub async fn resolve_role(
+    State(state): State<Arc<AppState>>,
+    Path(role): Path<String>,
+) -> ApiResult<ResolvedRole> {
+    let rules = { state.rules.read().unwrap().clone() };
+    let raw_devices = { state.live_devices.read().unwrap().clone() };
+
+    let resolved =
+        usb::service::resolve_role(&raw_devices, &rules, &role).ok_or(ApiError::NotFound)?;
+
+    Ok(ApiResponse::success(resolved))
+}
+
+/// `GET /api/env` 的查询参数
+#[derive(Debug, Deserialize)]
+pub struct EnvQuery {
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+/// `GET /api/env?format=shell|dotenv|systemd|json`
+/// 所有已绑定角色的环境变量，直接返回文本 (不包 ApiResponse)，方便容器 `curl ... > .env`
+pub async fn env(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EnvQuery>,
+) -> Result<Response, ApiError> {
+    let format = match query.format.as_deref() {
+        None => EnvFormat::default(),
+        Some(s) => s.parse().map_err(|_| ApiError::InvalidParam)?,
+    };
+
+    let rules = { state.rules.read().unwrap().clone() };
+    let raw_devices = { state.live_devices.read().unwrap().clone() };
+
+    let resolved = usb::service::resolve_roles(&raw_devices, &rules);
+    let vars = usb::env::env_vars(&resolved)
+        .map_err(|e| ApiError::Conflict.with_msg(format!("{:#}", e)))?;
+
+    let content_type = match format {
+        EnvFormat::Json => "application/json",
+        _ => "text/plain; charset=utf-8",
+    };
+    Ok((
+        [(header::CONTENT_TYPE, content_type)],
+        usb::env::render(&vars, format),
+    )
+        .into_response())
+}
+
+/// `GET /api/roles/{role}/wait` 的查询参数
+#[derive(Debug, Deserialize)]
+pub struct WaitQuery {
+    // "30s"、"2m" 或者纯数字 (秒)，默认 30 秒
+    #[serde(default)]
+    pub timeout: Option<String>,
+}
+
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+// 长轮询最多等待 1 小时，更长的等待应由客户端重试
+const MAX_WAIT_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// `GET /api/roles/{role}/wait?timeout=30s`
+/// 长轮询：角色绑定后立即返回解析结果；超时返回 Timeout，并在 msg 中列出仍然缺失的角色
+pub async fn wait_for_role(
+    State(state): State<Arc<AppState>>,
+    Path(role): Path<String>,
+    Query(query): Query<WaitQuery>,
+) -> ApiResult<ResolvedRole> {
+    let timeout = match query.timeout.as_deref() {
+        None => DEFAULT_WAIT_TIMEOUT,
+        Some(s) => parse_timeout(s).ok_or(ApiError::InvalidParam)?,
+    };
+    if timeout > MAX_WAIT_TIMEOUT {
+        return Err(ApiError::InvalidParam.with_msg(format!(
+            "timeout must not exceed {}",
+            humantime::format_duration(MAX_WAIT_TIMEOUT)
+        )));
+    }
+
+    if !state.rules.read().unwrap().iter().any(|r| r.role == role) {
+        return Err(ApiError::NotFound);
+    }
+
+    match usb::wait::wait_for_roles(&state, std::slice::from_ref(&role), timeout).await {
+        Ok(mut resolved) => Ok(ApiResponse::success(resolved.remove(0))),
+        Err(missing) => {
+            Err(ApiError::Timeout.with_msg(format!("roles still missing: {}", missing.join(", "))))
+        }
+    }
+}
+
+// 兼容纯数字 (秒) 和 humantime 格式
+fn parse_timeout(s: &str) -> Option<Duration> {
+    match s.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => humantime::parse_duration(s).ok(),
+    }
+}
+
+/// `POST /api/roles/{role}/unbind`
+/// 删除角色的规则，返回被删除的规则
+pub async fn unbind_role(
+    State(state): State<Arc<AppState>>,
+    Path(role): Path<String>,
+) -> ApiResult<DeviceConfig> {
+    let removed = usb::rules::remove_rule(&state, &role)?;
+    Ok(ApiResponse::success(removed))
+}
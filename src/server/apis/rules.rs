@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::{Deserialize, Deserializer, de};
+
+use crate::{
+    core::usb::{
+        self,
+        models::{DeviceConfig, RulesPreview},
+    },
+    infra::state::AppState,
+    server::response::{ApiResponse, ApiResult},
+};
+
+/// `GET /api/rules`
+pub async fn list_rules(State(state): State<Arc<AppState>>) -> ApiResult<Vec<DeviceConfig>> {
+    let rules = { state.rules.read().unwrap().clone() };
+    Ok(ApiResponse::success(rules))
+}
+
+/// `POST /api/rules` 的请求体：单条规则，或者 (兼容旧版客户端) 整个规则列表
+#[derive(Debug)]
+pub enum RulesBody {
+    Many(Vec<DeviceConfig>),
+    One(DeviceConfig),
+}
+
+// 按 JSON 类型分派，而不是 #[serde(untagged)]：保留具体字段的反序列化错误
+impl<'de> Deserialize<'de> for RulesBody {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let body = if value.is_array() {
+            serde_json::from_value(value).map(Self::Many)
+        } else {
+            serde_json::from_value(value).map(Self::One)
+        };
+        body.map_err(de::Error::custom)
+    }
+}
+
+/// `POST /api/rules`
+/// 新增一条规则，角色重复时返回 Conflict，返回新增的规则
+/// 兼容旧接口：请求体为数组时整体替换规则列表 (同 `PUT /api/rules`)，data 为空
+pub async fn create_rule(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<RulesBody>,
+) -> ApiResult<DeviceConfig> {
+    match body {
+        RulesBody::One(rule) => {
+            usb::rules::add_rule(&state, rule.clone())?;
+            Ok(ApiResponse::success(rule))
+        }
+        RulesBody::Many(new_rules) => {
+            usb::rules::replace_rules(&state, new_rules)?;
+            Ok(ApiResponse::ok())
+        }
+    }
+}
+
+/// `PUT /api/rules`
+/// 整体替换规则列表，空列表表示清空所有规则
+pub async fn replace_rules(
+    State(state): State<Arc<AppState>>,
+    Json(new_rules): Json<Vec<DeviceConfig>>,
+) -> ApiResult {
+    usb::rules::replace_rules(&state, new_rules)?;
+    Ok(ApiResponse::ok())
+}
+
+/// `POST /api/rules/preview`
+/// 用候选规则集对当前在线设备做一次匹配 (不保存)，
+/// 返回设备视图、会失去绑定的角色和冲突，避免保存后才发现摄像头掉线
+pub async fn preview_rules(
+    State(state): State<Arc<AppState>>,
+    Json(candidate): Json<Vec<DeviceConfig>>,
+) -> ApiResult<RulesPreview> {
+    let rules = { state.rules.read().unwrap().clone() };
+    let raw_devices = { state.live_devices.read().unwrap().clone() };
+
+    let preview = usb::service::preview_rules(&raw_devices, &rules, &candidate);
+
+    Ok(ApiResponse::success(preview))
+}
+
+/// `GET /api/rules/{role}`
+pub async fn get_rule(
+    State(state): State<Arc<AppState>>,
+    Path(role): Path<String>,
+) -> ApiResult<DeviceConfig> {
+    let rule = usb::rules::get_rule(&state, &role)?;
+    Ok(ApiResponse::success(rule))
+}
+
+/// `PUT /api/rules/{role}`
+/// 更新某个角色的规则，不存在时返回 NotFound
+pub async fn update_rule(
+    State(state): State<Arc<AppState>>,
+    Path(role): Path<String>,
+    Json(rule): Json<DeviceConfig>,
+) -> ApiResult<DeviceConfig> {
+    usb::rules::update_rule(&state, &role, rule.clone())?;
+    Ok(ApiResponse::success(rule))
+}
+
+/// `DELETE /api/rules/{role}`
+/// 返回被删除的规则
+pub async fn delete_rule(
+    State(state): State<Arc<AppState>>,
+    Path(role): Path<String>,
+) -> ApiResult<DeviceConfig> {
+    let removed = usb::rules::remove_rule(&state, &role)?;
+    Ok(ApiResponse::success(removed))
+}
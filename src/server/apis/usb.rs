@@ -1,11 +1,15 @@
 use std::sync::Arc;
 
-use axum::{Json, extract::State};
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::Deserialize;
 
 use crate::{
     core::usb::{
         self,
-        models::{DeviceConfig, DeviceView},
+        models::{BindStrategy, Conflict, DeviceExplanation, DeviceView},
     },
     infra::state::AppState,
     server::{
@@ -27,37 +31,48 @@ pub async fn list_devices(State(state): State<Arc<AppState>>) -> ApiResult<Vec<D
     Ok(ApiResponse::success(views))
 }
 
-pub async fn save_rules(
+/// `GET /api/devices/{id}/explain`
+/// 列出每条规则对该设备的逐项比对结果，用于排查设备为什么没有绑定
+pub async fn explain_device(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> ApiResult<DeviceExplanation> {
+    let rules = { state.rules.read().unwrap().clone() };
+    let raw_devices = { state.live_devices.read().unwrap().clone() };
+
+    let explanation =
+        usb::service::explain_device(&raw_devices, &rules, &id).ok_or(ApiError::NotFound)?;
+
+    Ok(ApiResponse::success(explanation))
+}
+
+/// `GET /api/conflicts`
+/// 当前规则与在线设备之间的歧义：一个角色命中多个设备，或一个设备命中多条同级规则
+pub async fn list_conflicts(State(state): State<Arc<AppState>>) -> ApiResult<Vec<Conflict>> {
+    let rules = { state.rules.read().unwrap().clone() };
+    let raw_devices = { state.live_devices.read().unwrap().clone() };
+
+    let conflicts = usb::service::detect_conflicts(&raw_devices, &rules);
+
+    Ok(ApiResponse::success(conflicts))
+}
+
+/// `POST /api/devices/{id}/bind` 的请求体
+#[derive(Debug, Deserialize)]
+pub struct BindRequest {
+    pub role: String,
+    // 不填时由服务端选择默认策略 (优先端口)
+    #[serde(default)]
+    pub strategy: Option<BindStrategy>,
+}
+
+/// `POST /api/devices/{id}/bind`
+/// 服务端根据在线设备的 RawDeviceInfo 生成规则并保存，返回绑定后的设备视图
+pub async fn bind_device(
     State(state): State<Arc<AppState>>,
-    Json(new_rules): Json<Vec<DeviceConfig>>,
-) -> ApiResult {
-    // 这里的 AppResult 默认泛型就是 ()
-
-    // ... 校验逻辑 ...
-    if new_rules.is_empty() {
-        return Err(ApiError::InvalidParam);
-    }
-
-    // ... 序列化和保存逻辑 ...
-    let json_str = serde_json::to_string_pretty(&new_rules).map_err(|e| {
-        tracing::error!("Serialize error: {}", e);
-        ApiError::Unknown
-    })?;
-
-    if let Err(e) = std::fs::write(&state.config_path, json_str) {
-        // 返回带 HTTP 500 的 ApiResponse
-        return Ok(ApiResponse::server_error(format!("写入失败: {}", e)));
-    }
-
-    // ... 更新内存 ...
-    {
-        let mut w = state.rules.write().unwrap();
-        *w = new_rules;
-    }
-
-    // ==========================================
-    // 之前: Ok(ApiResponse::success(()))
-    // 现在: 直接调用 ok()
-    // ==========================================
-    Ok(ApiResponse::ok())
+    Path(id): Path<String>,
+    Json(req): Json<BindRequest>,
+) -> ApiResult<DeviceView> {
+    let view = usb::rules::bind_device(&state, &id, &req.role, req.strategy)?;
+    Ok(ApiResponse::success(view))
 }
@@ -17,6 +17,7 @@ pub async fn index_page() -> Html<&'static str> {
             button { background: var(--primary); color: white; border: none; padding: 0.5rem 1rem; border-radius: 4px; cursor: pointer; font-weight: 500; }
             button:hover { opacity: 0.9; }
             button.save { background: #16a34a; }
+            button.danger { background: #dc2626; }
 
             /* 消息提示框样式 */
             #msg-box { padding: 10px; margin-bottom: 10px; border-radius: 4px; display: none; }
@@ -30,6 +31,7 @@ pub async fn index_page() -> Html<&'static str> {
             .badge { display: inline-block; padding: 0.25rem 0.5rem; border-radius: 999px; font-size: 0.75rem; font-weight: 600; }
             .badge.bound { background: #dcfce7; color: #166534; }
             .badge.unbound { background: #f1f5f9; color: #64748b; }
+            .badge.conflict { background: #fee2e2; color: #991b1b; }
             .info-row { font-size: 0.85em; color: #64748b; margin-top: 2px; }
         </style>
     </head>
@@ -38,7 +40,6 @@ pub async fn index_page() -> Html<&'static str> {
             <h1>🔌 DoraTool USB Resolver</h1>
             <div class="actions">
                 <button onclick="loadData()">Refresh Devices</button>
-                <button class="save" onclick="saveRules()">Save Configuration</button>
             </div>
         </div>
 
@@ -50,7 +51,7 @@ pub async fn index_page() -> Html<&'static str> {
                     <th style="width: 25%">Role (Unique)</th>
                     <th style="width: 40%">Device Info</th>
                     <th style="width: 20%">Binding Strategy</th>
-                    <th style="width: 15%">Status</th>
+                    <th style="width: 15%">Action</th>
                 </tr>
             </thead>
             <tbody id="device-list">
@@ -97,14 +98,28 @@ pub async fn index_page() -> Html<&'static str> {
                 } catch (e) { if (!silent) showMsg('Connection failed: ' + e, true); }
             }
 
+            // 平台拿不到端口路径时后端返回占位符 "N/A"，这种设备不能按端口绑定
+            function hasPortPath(dev) {
+                return !!dev.port_path && dev.port_path !== 'N/A';
+            }
+
+            // 默认选中设备实际可用的最精确策略：端口 > 序列号 > VID/PID
+            function defaultStrategy(dev) {
+                if (hasPortPath(dev)) return 'port';
+                if (dev.serial) return 'serial';
+                return 'vid_pid';
+            }
+
             function render() {
                 const tbody = document.getElementById('device-list');
                 tbody.innerHTML = currentDevices.map((dev, idx) => `
                     <tr>
                         <td>
                             <input type="text" id="role-${idx}"
-                                value="${dev.assigned_role || ''}"
+                                value="${dev.role || ''}"
                                 placeholder="e.g. top_camera"
+                                onfocus="pauseRefresh()" onblur="resumeRefresh()"
+                                ${dev.role ? 'disabled' : ''}
                                 style="font-weight: bold;">
                         </td>
                         <td>
@@ -113,32 +128,48 @@ pub async fn index_page() -> Html<&'static str> {
                             </div>
                             <div class="info-row">SN: ${dev.serial || 'N/A'}</div>
                             <div class="info-row">Port: ${dev.port_path}</div>
+                            ${(dev.conflicts || []).map(c => `
+                                <div class="info-row" style="color:#991b1b">
+                                    ⚠ ${c.kind === 'ambiguous_role'
+                                        ? `Role '${c.role}' matches ${c.devices.length} devices`
+                                        : `Rules tie: ${c.roles.join(', ')}`}
+                                </div>`).join('')}
                         </td>
                         <td>
-                            <select id="strategy-${idx}" style="padding:0.4rem">
-                                <option value="port" ${dev.port_path ? 'selected' : ''}>Bind by Port Path</option>
-                                <option value="serial" ${dev.serial ? '' : 'disabled'}>Bind by Serial</option>
+                            <select id="strategy-${idx}" style="padding:0.4rem" ${dev.role ? 'disabled' : ''}>
+                                <option value="port" ${hasPortPath(dev) ? '' : 'disabled'} ${defaultStrategy(dev) === 'port' ? 'selected' : ''}>Bind by Port Path</option>
+                                <option value="serial" ${dev.serial ? '' : 'disabled'} ${defaultStrategy(dev) === 'serial' ? 'selected' : ''}>Bind by Serial</option>
+                                <option value="topology" ${dev.topology ? '' : 'disabled'}>Bind by Topology (bus-independent)</option>
+                                <option value="vid_pid" ${defaultStrategy(dev) === 'vid_pid' ? 'selected' : ''}>Bind by VID/PID only</option>
                             </select>
                         </td>
                         <td>
-                            <span class="badge ${dev.assigned_role ? 'bound' : 'unbound'}">
-                                ${dev.assigned_role ? 'BOUND' : 'NEW'}
-                            </span>
+                            ${(dev.conflicts || []).length
+                                ? `<span class="badge conflict">CONFLICT</span>`
+                                : dev.role
+                                ? `<span class="badge bound">BOUND</span>
+                                   <button class="danger" onclick="unbindRole(${idx})">Unbind</button>`
+                                : `<span class="badge unbound">NEW</span>
+                                   <button class="save" onclick="bindDevice(${idx})">Bind</button>`}
                         </td>
                     </tr>
                 `).join('');
             }
 
-            let refreshTimer = null;
+            let eventSource = null;
 
             function startAutoRefresh() {
-                if (refreshTimer) clearInterval(refreshTimer);
-                // 每 1000 毫秒 (1秒) 自动拉取一次数据
-                refreshTimer = setInterval(() => {
+                if (eventSource) eventSource.close();
+                // 订阅服务端推送 (SSE)，设备或角色有变化时才重新拉取数据
+                // 断线后浏览器会自动重连，并通过 Last-Event-ID 补发错过的事件
+                eventSource = new EventSource('/api/events');
+                eventSource.onmessage = () => {
                     if (isAutoRefreshing) {
                         loadData(true); // true 表示静默加载，不弹窗报错
                     }
-                }, 1000);
+                };
+                // 错过的事件无法补发时，服务端会发送 resync，直接全量刷新
+                eventSource.addEventListener('resync', () => loadData(true));
             }
 
             function pauseRefresh() {
@@ -157,64 +188,39 @@ pub async fn index_page() -> Html<&'static str> {
             loadData();
             startAutoRefresh();
 
-            async function saveRules() {
-                const rules = [];
-                const roles = new Set();
-
-                for (let idx = 0; idx < currentDevices.length; idx++) {
-                    const dev = currentDevices[idx];
-                    const roleInput = document.getElementById(`role-${idx}`).value.trim();
-
-                    if (!roleInput) continue;
-
-                    if (roles.has(roleInput)) {
-                        showMsg(`Duplicate role '${roleInput}' detected!`, true);
-                        return;
-                    }
-                    roles.add(roleInput);
-
-                    const strategy = document.getElementById(`strategy-${idx}`).value;
-
-                    // 这里要注意：发给后端的是 DeviceConfig 结构
-                    // 前端的 dev.vid 是 String ("0x1234")，但 DeviceConfig 需要 u16
-                    // 所以我们需要在前端或者后端处理。
-                    // 鉴于之前的设计：前端展示 String，后端保存 u16。
-                    // 实际上，DeviceConfig 需要 u16。
-                    // 最简单的做法：前端把原始的 hex string 转回 int 发给后端，
-                    // 或者后端做宽容处理。
-
-                    // 修正：DeviceView 中的 vid 是 String ("0x...")
-                    // 我们可以直接 parse 回去
-
-                    const rule = {
-                        role: roleInput,
-                        // 去掉 0x 并转为整数
-                        vid: parseInt(dev.vid, 16),
-                        pid: parseInt(dev.pid, 16),
-                        serial: null,
-                        port_path: null
-                    };
-
-                    if (strategy === 'serial' && dev.serial) {
-                        rule.serial = dev.serial;
-                    } else if (dev.port_path) {
-                        rule.port_path = dev.port_path;
-                    }
-                    rules.push(rule);
+            // 规则由服务端根据设备信息生成，前端只提交设备 id、角色和策略
+            async function bindDevice(idx) {
+                const dev = currentDevices[idx];
+                const role = document.getElementById(`role-${idx}`).value.trim();
+                if (!role) {
+                    showMsg('Please enter a role name first', true);
+                    return;
                 }
+                const strategy = document.getElementById(`strategy-${idx}`).value;
 
-                const res = await fetch('/api/rules', {
+                const res = await fetch(`/api/devices/${encodeURIComponent(dev.id)}/bind`, {
                     method: 'POST',
                     headers: {'Content-Type': 'application/json'},
-                    body: JSON.stringify(rules)
+                    body: JSON.stringify({ role, strategy })
                 });
+                await handleResult(res, `Bound '${role}'`);
+            }
+
+            async function unbindRole(idx) {
+                const role = currentDevices[idx].role;
+                const res = await fetch(`/api/roles/${encodeURIComponent(role)}/unbind`, {
+                    method: 'POST'
+                });
+                await handleResult(res, `Unbound '${role}'`);
+            }
 
+            async function handleResult(res, okMsg) {
                 const ret = await res.json();
-                if(ret.code === 0) {
-                    showMsg('Saved successfully!', false);
-                    loadData();
+                if (ret.code === 0) {
+                    showMsg(okMsg, false);
+                    loadData(true);
                 } else {
-                    showMsg(ret.message, true);
+                    showMsg(ret.msg, true);
                 }
             }
 
@@ -0,0 +1,219 @@
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{
+    extract::{
+        State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info};
+
+use crate::{
+    core::usb::{
+        self,
+        models::{BindStrategy, DeviceView},
+    },
+    infra::state::AppState,
+    server::{error::ApiError, response::ApiResponse},
+};
+
+/// 客户端发来的请求
+/// `id` 由客户端生成，服务端在响应里原样带回，用于关联请求和响应
+#[derive(Debug, Deserialize)]
+struct WsRequest {
+    #[serde(default)]
+    id: Value,
+    #[serde(flatten)]
+    command: WsCommand,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum WsCommand {
+    // 订阅指定角色的设备更新，roles 为空表示订阅全部设备
+    Subscribe {
+        #[serde(default)]
+        roles: Vec<String>,
+    },
+    // 把在线设备绑定到角色 (规则由服务端生成，同 POST /api/devices/{id}/bind)
+    Bind {
+        device_id: String,
+        role: String,
+        #[serde(default)]
+        strategy: Option<BindStrategy>,
+    },
+    // 删除某个角色的规则
+    Unbind {
+        role: String,
+    },
+    // 立即扫描一次
+    Rescan,
+}
+
+/// 服务端发出的消息
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum WsMessage {
+    // 对某个请求的响应，沿用 ApiResponse 的 code/msg/data 结构
+    Response {
+        id: Value,
+        #[serde(flatten)]
+        body: ApiResponse<Value>,
+    },
+    // 订阅范围内的设备列表发生变化
+    Devices {
+        devices: Vec<DeviceView>,
+    },
+}
+
+/// 单个连接的订阅状态
+#[derive(Default)]
+struct Subscription {
+    active: bool,
+    // 为空表示全部设备
+    roles: HashSet<String>,
+    // 上一次推送的内容，没变化就不重复推送
+    last_sent: Option<String>,
+}
+
+/// `GET /api/ws`
+/// 双向控制通道：订阅设备更新，并发送 bind / unbind / rescan 命令
+pub async fn ws_handler(State(state): State<Arc<AppState>>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, state))
+}
+
+async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
+    info!("🔗 WebSocket 客户端已连接");
+
+    let mut events = state.subscribe();
+    let mut subscription = Subscription::default();
+
+    loop {
+        tokio::select! {
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    // Ping/Pong 由 axum 自动处理，二进制消息忽略
+                    Some(Ok(_)) => continue,
+                };
+
+                let reply = handle_request(&state, &mut subscription, text.as_str());
+                if send(&mut socket, &reply).await.is_err() {
+                    break;
+                }
+
+                // 订阅后立即推送一次当前状态
+                if push_devices(&mut socket, &state, &mut subscription).await.is_err() {
+                    break;
+                }
+            }
+            event = events.recv() => {
+                match event {
+                    // Lagged 说明错过了一些事件，重新计算一次即可
+                    Ok(_) | Err(RecvError::Lagged(_)) => {
+                        if push_devices(&mut socket, &state, &mut subscription).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
+    info!("🔌 WebSocket 客户端已断开");
+}
+
+fn handle_request(state: &AppState, subscription: &mut Subscription, text: &str) -> WsMessage {
+    let request: WsRequest = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => {
+            debug!("Invalid ws request: {}", e);
+            // 尽量把 id 带回去，方便客户端定位是哪条请求出错
+            let id = serde_json::from_str::<Value>(text)
+                .ok()
+                .and_then(|v| v.get("id").cloned())
+                .unwrap_or(Value::Null);
+            return response(id, Err(ApiError::InvalidParam));
+        }
+    };
+
+    let result = match request.command {
+        WsCommand::Subscribe { roles } => {
+            subscription.active = true;
+            subscription.roles = roles.into_iter().collect();
+            subscription.last_sent = None;
+            Ok(Value::Null)
+        }
+        WsCommand::Bind {
+            device_id,
+            role,
+            strategy,
+        } => usb::rules::bind_device(state, &device_id, &role, strategy)
+            .map(|view| serde_json::to_value(view).unwrap_or_default())
+            .map_err(ApiError::from),
+        WsCommand::Unbind { role } => usb::rules::remove_rule(state, &role)
+            .map(|_| Value::Null)
+            .map_err(ApiError::from),
+        WsCommand::Rescan => {
+            state.request_rescan();
+            Ok(Value::Null)
+        }
+    };
+
+    response(request.id, result)
+}
+
+fn response(id: Value, result: Result<Value, ApiError>) -> WsMessage {
+    let body = match result {
+        Ok(Value::Null) => ApiResponse::ok(),
+        Ok(data) => ApiResponse::success(data),
+        Err(e) => ApiResponse::error(e.code(), e.msg().to_string()),
+    };
+    WsMessage::Response { id, body }
+}
+
+/// 计算订阅范围内的设备列表，有变化才推送
+async fn push_devices(
+    socket: &mut WebSocket,
+    state: &AppState,
+    subscription: &mut Subscription,
+) -> Result<(), axum::Error> {
+    if !subscription.active {
+        return Ok(());
+    }
+
+    let rules = { state.rules.read().unwrap().clone() };
+    let raw_devices = { state.live_devices.read().unwrap().clone() };
+
+    let devices: Vec<DeviceView> = usb::service::match_raw_to_views(&raw_devices, &rules)
+        .into_iter()
+        .filter(|view| {
+            subscription.roles.is_empty()
+                || view
+                    .role
+                    .as_ref()
+                    .is_some_and(|role| subscription.roles.contains(role))
+        })
+        .collect();
+
+    let message = WsMessage::Devices { devices };
+    let text = serde_json::to_string(&message).unwrap_or_default();
+    if subscription.last_sent.as_ref() == Some(&text) {
+        return Ok(());
+    }
+
+    socket.send(Message::Text(text.clone().into())).await?;
+    subscription.last_sent = Some(text);
+    Ok(())
+}
+
+async fn send(socket: &mut WebSocket, message: &WsMessage) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).unwrap_or_default();
+    socket.send(Message::Text(text.into())).await
+}
@@ -1,4 +1,4 @@
-use crate::server::response::ApiResponse;
+use crate::{core::usb::rules::RuleError, server::response::ApiResponse};
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -19,6 +19,8 @@ macro_rules! define_api_error {
                 $(#[$docs])*
                 $variant,
             )+
+            /// 带具体原因的错误：错误码和 HTTP 状态取自内部的错误，msg 使用具体原因
+            WithMsg(Box<ApiError>, String),
         }
 
         impl ApiError {
@@ -29,16 +31,18 @@ macro_rules! define_api_error {
                     $(
                         Self::$variant => $code,
                     )+
+                    Self::WithMsg(kind, _) => kind.code(),
                 }
             }
 
             // Get error information
             // 获取错误信息
-            pub fn msg(&self) -> &'static str {
+            pub fn msg(&self) -> &str {
                 match self {
                     $(
                         Self::$variant => $msg,
                     )+
+                    Self::WithMsg(_, msg) => msg,
                 }
             }
 
@@ -49,8 +53,15 @@ macro_rules! define_api_error {
                     $(
                         Self::$variant => $status,
                     )+
+                    Self::WithMsg(kind, _) => kind.status(),
                 }
             }
+
+            // Attach the specific reason, shown to clients instead of the generic message
+            // 附加具体原因，客户端看到的 msg 是它而不是通用的错误信息
+            pub fn with_msg(self, msg: impl Into<String>) -> Self {
+                Self::WithMsg(Box::new(self), msg.into())
+            }
         }
 
         // Implementing IntoResponse
@@ -83,6 +94,10 @@ define_api_error! {
     /// 403 权限不足
     (PermissionDenied, 1004, "Permission Denied", StatusCode::FORBIDDEN);
 
+    /// 等待超时 (例如长轮询或学习模式没有等到设备)
+    /// 这不是请求本身的错误，HTTP 状态仍为 200，只在 code 中区分，避免代理或客户端自动重试
+    (Timeout, 1005, "Wait Timed Out", StatusCode::OK);
+
     /// 500 数据库错误
     (DbError, 2001, "Database Error", StatusCode::INTERNAL_SERVER_ERROR);
 
@@ -92,3 +107,21 @@ define_api_error! {
     /// 500 唯一性冲突 (例如名称重复)
     (Conflict, 2002, "Resource Already Exists", StatusCode::CONFLICT);
 }
+
+// Mapping of rule-store errors
+// 规则存储错误的映射
+impl From<RuleError> for ApiError {
+    fn from(err: RuleError) -> Self {
+        // 存储错误只记录日志，其余错误把原因带给客户端 (例如哪个角色已存在)
+        let kind = match &err {
+            RuleError::Conflict(_) => Self::Conflict,
+            RuleError::NotFound(_) | RuleError::DeviceNotFound(_) => Self::NotFound,
+            RuleError::Invalid(_) => Self::InvalidParam,
+            RuleError::Storage(e) => {
+                tracing::error!("Save rules error: {:#}", e);
+                return Self::Unknown;
+            }
+        };
+        kind.with_msg(err.to_string())
+    }
+}
@@ -22,8 +22,44 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         // --- API 接口 ---
         // 获取设备列表
         .route("/api/devices", get(apis::usb::list_devices))
-        // 保存规则配置
-        .route("/api/rules", post(apis::usb::save_rules))
+        // 把在线设备绑定到角色 (服务端生成规则)
+        .route("/api/devices/{id}/bind", post(apis::usb::bind_device))
+        // 解释设备的匹配过程 (每条规则的逐项比对)
+        .route("/api/devices/{id}/explain", get(apis::usb::explain_device))
+        // 规则/设备冲突报告
+        .route("/api/conflicts", get(apis::usb::list_conflicts))
+        // 已绑定角色的环境变量导出
+        .route("/api/env", get(apis::roles::env))
+        // 角色状态 (在线/离线)
+        .route("/api/roles", get(apis::roles::list_roles))
+        // 解析角色当前对应的设备路径
+        .route("/api/roles/{role}/resolve", get(apis::roles::resolve_role))
+        // 长轮询：等待角色上线
+        .route("/api/roles/{role}/wait", get(apis::roles::wait_for_role))
+        // 解除角色绑定
+        .route("/api/roles/{role}/unbind", post(apis::roles::unbind_role))
+        // 学习模式：等待下一个插入的设备并绑定到角色
+        .route("/api/learn", post(apis::learn::learn))
+        // 设备/角色变化的实时推送 (SSE)
+        .route("/api/events", get(apis::events::event_stream))
+        // 双向控制通道 (WebSocket)
+        .route("/api/ws", get(apis::ws::ws_handler))
+        // 规则配置: 列表 / 新增 (旧版客户端 POST 数组仍为整体替换) / 整体替换
+        .route(
+            "/api/rules",
+            get(apis::rules::list_rules)
+                .post(apis::rules::create_rule)
+                .put(apis::rules::replace_rules),
+        )
+        // 预览候选规则集 (dry-run，不保存)
+        .route("/api/rules/preview", post(apis::rules::preview_rules))
+        // 单条规则: 查询 / 更新 / 删除
+        .route(
+            "/api/rules/{role}",
+            get(apis::rules::get_rule)
+                .put(apis::rules::update_rule)
+                .delete(apis::rules::delete_rule),
+        )
         // --- 中间件 ---
         .layer(TraceLayer::new_for_http()) // HTTP 请求日志
         .layer(cors) // 跨域支持